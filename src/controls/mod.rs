@@ -0,0 +1,331 @@
+use bevy::{
+    input::mouse::{MouseScrollUnit, MouseWheel},
+    prelude::*,
+};
+
+use crate::{
+    AppState,
+    input::{Action, Binding, Bindings},
+    style::{ThemeColor, ThemedBackground, ThemedBorder, ThemedText},
+};
+
+const ROW_HEIGHT: f32 = 28.;
+const MODIFIER_KEYS: [KeyCode; 4] = [
+    KeyCode::ControlLeft,
+    KeyCode::ControlRight,
+    KeyCode::ShiftLeft,
+    KeyCode::ShiftRight,
+];
+
+pub(super) fn plugin(app: &mut App) {
+    app.init_resource::<Rebinding>()
+        .add_systems(OnEnter(AppState::Controls), setup_controls)
+        .add_systems(OnExit(AppState::Controls), clean_up_controls)
+        .add_systems(
+            Update,
+            (
+                scroll_controls_list,
+                capture_binding,
+                update_binding_texts
+                    .run_if(resource_changed::<Bindings>.or(resource_changed::<Rebinding>)),
+            )
+                .chain()
+                .run_if(in_state(AppState::Controls)),
+        );
+}
+
+#[derive(Component)]
+struct ControlsComponent;
+
+/// The action waiting for a new key or button.
+#[derive(Resource, Default)]
+struct Rebinding(Option<Action>);
+
+#[derive(Component)]
+struct BindingText(Action);
+
+#[derive(Component)]
+struct ControlsList;
+
+fn setup_controls(mut commands: Commands, mut rebinding: ResMut<Rebinding>) {
+    rebinding.0 = None;
+
+    commands.spawn((Camera2d, ControlsComponent));
+    commands
+        .spawn((
+            ControlsComponent,
+            Node {
+                justify_self: JustifySelf::Center,
+                align_self: AlignSelf::Center,
+                flex_direction: FlexDirection::Column,
+                row_gap: Val::Px(8.),
+                width: Val::Px(640.),
+                max_height: Val::Percent(90.),
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                Text::new("Controls"),
+                TextFont {
+                    font_size: 48.0,
+                    ..default()
+                },
+                ThemedText(ThemeColor::OnBackground),
+            ));
+            parent.spawn((
+                Text::new(
+                    "Click a binding, then press a key or a button. Click it again to cancel.",
+                ),
+                TextFont {
+                    font_size: 16.0,
+                    ..default()
+                },
+                ThemedText(ThemeColor::Muted),
+            ));
+
+            parent
+                .spawn((
+                    ControlsList,
+                    Node {
+                        flex_direction: FlexDirection::Column,
+                        row_gap: Val::Px(2.),
+                        min_height: Val::Px(0.),
+                        overflow: Overflow::scroll_y(),
+                        ..default()
+                    },
+                ))
+                .with_children(|list| {
+                    for action in Action::ALL {
+                        spawn_action_row(list, action);
+                    }
+                });
+
+            parent
+                .spawn(Node {
+                    column_gap: Val::Px(8.),
+                    ..default()
+                })
+                .with_children(|buttons| {
+                    spawn_button(buttons, "Reset to defaults").observe(
+                        |_: Trigger<Pointer<Click>>,
+                         mut bindings: ResMut<Bindings>,
+                         mut rebinding: ResMut<Rebinding>| {
+                            *bindings = Bindings::default();
+                            rebinding.0 = None;
+                        },
+                    );
+                    spawn_button(buttons, "Back").observe(
+                        |_: Trigger<Pointer<Click>>, mut commands: Commands| {
+                            commands.set_state(AppState::MainMenu);
+                        },
+                    );
+                });
+        });
+}
+
+fn spawn_action_row(parent: &mut ChildSpawnerCommands, action: Action) {
+    parent
+        .spawn(Node {
+            height: Val::Px(ROW_HEIGHT),
+            flex_shrink: 0.,
+            justify_content: JustifyContent::SpaceBetween,
+            align_items: AlignItems::Center,
+            ..default()
+        })
+        .with_children(|row| {
+            row.spawn((
+                Text::new(action.label()),
+                TextFont {
+                    font_size: 18.0,
+                    ..default()
+                },
+                ThemedText(ThemeColor::OnBackground),
+            ));
+
+            row.spawn(Node {
+                height: Val::Percent(100.),
+                column_gap: Val::Px(4.),
+                ..default()
+            })
+            .with_children(|controls| {
+                controls
+                    .spawn((
+                        Button,
+                        Node {
+                            width: Val::Px(300.),
+                            height: Val::Percent(100.),
+                            padding: UiRect::horizontal(Val::Px(8.)),
+                            align_items: AlignItems::Center,
+                            ..default()
+                        },
+                        ThemedBackground::new(ThemeColor::Surface),
+                        BorderRadius::all(Val::Px(4.)),
+                    ))
+                    .with_child((
+                        BindingText(action),
+                        Text::new(""),
+                        TextFont {
+                            font_size: 16.0,
+                            ..default()
+                        },
+                        ThemedText(ThemeColor::OnSurface),
+                        Pickable::IGNORE,
+                    ))
+                    .observe(
+                        move |_: Trigger<Pointer<Click>>, mut rebinding: ResMut<Rebinding>| {
+                            // Every key can be bound, so clicking again is the way to cancel.
+                            rebinding.0 = if rebinding.0 == Some(action) {
+                                None
+                            } else {
+                                Some(action)
+                            };
+                        },
+                    );
+
+                controls
+                    .spawn((
+                        Button,
+                        Node {
+                            width: Val::Px(56.),
+                            height: Val::Percent(100.),
+                            justify_content: JustifyContent::Center,
+                            align_items: AlignItems::Center,
+                            ..default()
+                        },
+                        ThemedBackground::new(ThemeColor::Surface),
+                        BorderRadius::all(Val::Px(4.)),
+                    ))
+                    .with_child((
+                        Text::new("Clear"),
+                        TextFont {
+                            font_size: 16.0,
+                            ..default()
+                        },
+                        ThemedText(ThemeColor::OnSurface),
+                        Pickable::IGNORE,
+                    ))
+                    .observe(
+                        move |_: Trigger<Pointer<Click>>,
+                              mut bindings: ResMut<Bindings>,
+                              mut rebinding: ResMut<Rebinding>| {
+                            bindings.clear(action);
+                            rebinding.0 = None;
+                        },
+                    );
+            });
+        });
+}
+
+fn spawn_button<'a>(parent: &'a mut ChildSpawnerCommands, label: &str) -> EntityCommands<'a> {
+    let mut button = parent.spawn((
+        Button,
+        Node {
+            height: Val::Px(48.),
+            flex_grow: 1.,
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            ..default()
+        },
+        ThemedBorder(ThemeColor::Primary),
+        ThemedBackground::new(ThemeColor::ContainerPrimary),
+    ));
+    button.with_child((
+        Text::new(label),
+        TextFont {
+            font_size: 24.0,
+            ..default()
+        },
+        ThemedText(ThemeColor::OnPrimary),
+        Pickable::IGNORE,
+    ));
+    button
+}
+
+/// Binds the first key or button pressed while an action is waiting for one. Modifiers alone don't
+/// count, they're combined with the key pressed after them.
+fn capture_binding(
+    keys: Res<ButtonInput<KeyCode>>,
+    mouse_buttons: Res<ButtonInput<MouseButton>>,
+    gamepads: Query<&Gamepad>,
+    mut rebinding: ResMut<Rebinding>,
+    mut bindings: ResMut<Bindings>,
+) {
+    let Some(action) = rebinding.0 else {
+        return;
+    };
+
+    let control = keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+    let shift = keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+
+    let key = keys
+        .get_just_pressed()
+        .find(|key| !MODIFIER_KEYS.contains(key))
+        .map(|key| match (control, shift) {
+            (true, true) => Binding::ControlShiftKey(*key),
+            (true, false) => Binding::ControlKey(*key),
+            (false, _) => Binding::Key(*key),
+        });
+    // The left button is kept for clicking and drawing lines.
+    let mouse_button = mouse_buttons
+        .get_just_pressed()
+        .find(|button| **button != MouseButton::Left)
+        .map(|button| Binding::Mouse(*button));
+    let gamepad_button = gamepads
+        .iter()
+        .find_map(|gamepad| gamepad.get_just_pressed().next())
+        .map(|button| Binding::Gamepad(*button));
+
+    let Some(binding) = key.or(mouse_button).or(gamepad_button) else {
+        return;
+    };
+
+    bindings.rebind(action, binding);
+    rebinding.0 = None;
+}
+
+/// UI nodes don't scroll by themselves.
+fn scroll_controls_list(
+    mut wheel_events: EventReader<MouseWheel>,
+    mut list: Single<&mut ScrollPosition, With<ControlsList>>,
+) {
+    for event in wheel_events.read() {
+        list.offset_y -= match event.unit {
+            MouseScrollUnit::Line => event.y * ROW_HEIGHT,
+            MouseScrollUnit::Pixel => event.y,
+        };
+    }
+}
+
+fn update_binding_texts(
+    mut texts_q: Query<(&BindingText, &mut Text)>,
+    bindings: Res<Bindings>,
+    rebinding: Res<Rebinding>,
+) {
+    for (BindingText(action), mut text) in &mut texts_q {
+        text.0 = if rebinding.0 == Some(*action) {
+            "Press a key or a button, or click to cancel...".to_string()
+        } else {
+            let labels = bindings
+                .get(*action)
+                .iter()
+                .map(Binding::label)
+                .collect::<Vec<_>>();
+
+            if labels.is_empty() {
+                "Not bound".to_string()
+            } else {
+                labels.join(", ")
+            }
+        };
+    }
+}
+
+fn clean_up_controls(
+    mut commands: Commands,
+    controls_components_query: Query<Entity, With<ControlsComponent>>,
+) {
+    for entity in controls_components_query.iter() {
+        commands.entity(entity).despawn();
+    }
+}
@@ -0,0 +1,337 @@
+use bevy::{
+    input::{
+        gestures::PinchGesture,
+        mouse::{AccumulatedMouseMotion, MouseScrollUnit, MouseWheel},
+        touch::TouchInput,
+    },
+    prelude::*,
+    window::PrimaryWindow,
+};
+
+use crate::{
+    AppState,
+    input::{Action, ActionInput},
+};
+
+use super::metro::{MAP_SIZE, Metro};
+
+/// How far the view can be zoomed in, relative to fitting the whole map.
+const MAX_ZOOM: f32 = 4.;
+/// Zoom multiplier for one line of the mouse wheel, or one press of `Action::ZoomIn`.
+const WHEEL_ZOOM_STEP: f32 = 1.15;
+/// Touchpads scroll in pixels, this many of them make up one line.
+const PIXELS_PER_LINE: f32 = 100.;
+const PAN_ACTIONS: [(Action, Vec2); 4] = [
+    (Action::PanUp, Vec2::Y),
+    (Action::PanLeft, Vec2::NEG_X),
+    (Action::PanDown, Vec2::NEG_Y),
+    (Action::PanRight, Vec2::X),
+];
+/// Screen pixels per real second, so that panning works the same while the game is paused.
+const KEYBOARD_PAN_SPEED: f32 = 600.;
+/// Space (in world units) left around the network when fitting the view to it.
+const FIT_NETWORK_PADDING: f32 = 20.;
+
+pub(super) fn plugin(app: &mut App) {
+    app.init_resource::<CameraView>()
+        .add_systems(OnEnter(AppState::Game), reset_camera_view)
+        .add_systems(
+            Update,
+            (
+                zoom_camera,
+                pan_camera,
+                pan_and_pinch_camera.run_if(on_event::<TouchInput>),
+                fit_network,
+                update_camera,
+            )
+                .chain()
+                .run_if(in_state(AppState::Game)),
+        );
+}
+
+/// How far the game view is zoomed in. At 1.0 the whole map fits the window.
+#[derive(Resource)]
+pub struct CameraView {
+    pub zoom: f32,
+}
+
+impl Default for CameraView {
+    fn default() -> Self {
+        Self { zoom: 1. }
+    }
+}
+
+fn reset_camera_view(mut camera_view: ResMut<CameraView>) {
+    *camera_view = CameraView::default();
+}
+
+/// The orthographic scale at which `area` fits the window.
+fn fit_scale(window_size: Vec2, area: Vec2) -> f32 {
+    if area.x / area.y > window_size.x / window_size.y {
+        area.x / window_size.x
+    } else {
+        area.y / window_size.y
+    }
+}
+
+/// Zooms around the cursor, so that the point under it stays in place.
+fn zoom_camera(
+    mut wheel_events: EventReader<MouseWheel>,
+    mut pinch_events: EventReader<PinchGesture>,
+    input: ActionInput,
+    camera_q: Single<(&Camera, &GlobalTransform, &mut Transform), With<Camera2d>>,
+    window: Single<&Window, With<PrimaryWindow>>,
+    mut camera_view: ResMut<CameraView>,
+) {
+    let lines = wheel_events
+        .read()
+        .map(|event| match event.unit {
+            MouseScrollUnit::Line => event.y,
+            MouseScrollUnit::Pixel => event.y / PIXELS_PER_LINE,
+        })
+        .sum::<f32>()
+        + input.just_pressed(Action::ZoomIn) as i32 as f32
+        - input.just_pressed(Action::ZoomOut) as i32 as f32;
+    let pinch = pinch_events.read().map(|event| event.0).sum::<f32>();
+
+    let factor = WHEEL_ZOOM_STEP.powf(lines) * (1. + pinch);
+    if factor == 1. {
+        return;
+    }
+
+    let old_zoom = camera_view.zoom;
+    camera_view.zoom = (old_zoom * factor).clamp(1., MAX_ZOOM);
+
+    let (camera, camera_transform, mut transform) = camera_q.into_inner();
+    let Some(cursor_position) = window.cursor_position() else {
+        return;
+    };
+    let Ok(cursor_position) = camera.viewport_to_world_2d(camera_transform, cursor_position) else {
+        return;
+    };
+
+    // The scale is inversely proportional to the zoom, and so is the distance on the screen
+    // between the camera and the cursor.
+    let position = cursor_position
+        + (transform.translation.truncate() - cursor_position) * old_zoom / camera_view.zoom;
+    transform.translation = position.extend(transform.translation.z);
+}
+
+/// Drags the map with the mouse, or moves the view with the keyboard.
+fn pan_camera(
+    camera_q: Single<(&Projection, &mut Transform), With<Camera2d>>,
+    mouse_motion: Res<AccumulatedMouseMotion>,
+    input: ActionInput,
+    time: Res<Time<Real>>,
+) {
+    let (projection, mut transform) = camera_q.into_inner();
+    let Projection::Orthographic(projection) = projection else {
+        return;
+    };
+
+    // In screen pixels, but with y pointing up.
+    let mut delta = Vec2::ZERO;
+
+    if input.pressed(Action::DragView) {
+        delta += Vec2::new(-mouse_motion.delta.x, mouse_motion.delta.y);
+    }
+
+    for (action, direction) in PAN_ACTIONS {
+        if input.pressed(action) {
+            delta += direction * KEYBOARD_PAN_SPEED * time.delta_secs();
+        }
+    }
+
+    if delta != Vec2::ZERO {
+        transform.translation += (delta * projection.scale).extend(0.);
+    }
+}
+
+/// Pans with two fingers, and zooms by pinching them. The point under the middle of the fingers
+/// follows them. Only runs when there are new touch events, otherwise the previous positions of
+/// the touches are stale.
+fn pan_and_pinch_camera(
+    camera_q: Single<(&Camera, &GlobalTransform, &mut Transform), With<Camera2d>>,
+    touches: Res<Touches>,
+    mut camera_view: ResMut<CameraView>,
+) {
+    let mut pressed = touches.iter();
+    let (Some(first), Some(second)) = (pressed.next(), pressed.next()) else {
+        return;
+    };
+
+    let previous_distance = first
+        .previous_position()
+        .distance(second.previous_position());
+    let distance = first.position().distance(second.position());
+    let old_zoom = camera_view.zoom;
+    if previous_distance > 0. {
+        camera_view.zoom = (old_zoom * distance / previous_distance).clamp(1., MAX_ZOOM);
+    }
+
+    let (camera, camera_transform, mut transform) = camera_q.into_inner();
+    let previous_middle = first
+        .previous_position()
+        .midpoint(second.previous_position());
+    let middle = first.position().midpoint(second.position());
+    let (Ok(previous_middle), Ok(middle)) = (
+        camera.viewport_to_world_2d(camera_transform, previous_middle),
+        camera.viewport_to_world_2d(camera_transform, middle),
+    ) else {
+        return;
+    };
+
+    // Both points are projected with the old scale, so the offset of the new middle from the
+    // camera has to be rescaled to the new zoom.
+    let position = transform.translation.truncate();
+    let position = previous_middle - (middle - position) * old_zoom / camera_view.zoom;
+    transform.translation = position.extend(transform.translation.z);
+}
+
+/// Zooms the view so that all the stations on lines are visible, or the whole map if there are no
+/// lines yet.
+fn fit_network(
+    camera_transform: Single<&mut Transform, With<Camera2d>>,
+    window: Single<&Window, With<PrimaryWindow>>,
+    input: ActionInput,
+    metro: Res<Metro>,
+    mut camera_view: ResMut<CameraView>,
+) {
+    if !input.just_pressed(Action::FitNetwork) {
+        return;
+    }
+
+    let positions = metro
+        .lines
+        .values()
+        .flatten()
+        .map(|station_id| metro.stations[*station_id].position)
+        .collect::<Vec<_>>();
+
+    let network = if positions.is_empty() {
+        Rect::from_center_size(Vec2::ZERO, MAP_SIZE)
+    } else {
+        let min = positions.iter().copied().fold(Vec2::MAX, Vec2::min);
+        let max = positions.iter().copied().fold(Vec2::MIN, Vec2::max);
+        Rect::from_corners(min, max).inflate(FIT_NETWORK_PADDING)
+    };
+
+    let window_size = window.size();
+    camera_view.zoom = (fit_scale(window_size, MAP_SIZE) / fit_scale(window_size, network.size()))
+        .clamp(1., MAX_ZOOM);
+
+    let mut camera_transform = camera_transform.into_inner();
+    camera_transform.translation = network.center().extend(camera_transform.translation.z);
+}
+
+/// Applies the zoom, and keeps the view within the map.
+fn update_camera(
+    camera_q: Single<(&mut Projection, &mut Transform), With<Camera2d>>,
+    window: Single<&Window, With<PrimaryWindow>>,
+    camera_view: Res<CameraView>,
+) {
+    let window_size = window.size();
+    // Minimised.
+    if window_size.min_element() <= 0. {
+        return;
+    }
+
+    let (mut projection, mut transform) = camera_q.into_inner();
+    let scale = fit_scale(window_size, MAP_SIZE) / camera_view.zoom;
+
+    let Projection::Orthographic(orthographic) = &*projection else {
+        return;
+    };
+    if orthographic.scale != scale {
+        *projection = Projection::Orthographic(OrthographicProjection {
+            scale,
+            ..OrthographicProjection::default_2d()
+        });
+    }
+
+    // Along the axis where the view is wider than the map, the map is kept centred.
+    let max_offset = (MAP_SIZE / 2. - window_size * scale / 2.).max(Vec2::ZERO);
+    let position = transform.translation.truncate();
+    let clamped = position.clamp(-max_offset, max_offset);
+
+    if clamped != position {
+        transform.translation = clamped.extend(transform.translation.z);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::input::touch::TouchPhase;
+
+    use super::*;
+    use crate::testing::{WINDOW_SIZE, headless_app, touch};
+
+    fn camera_app() -> App {
+        let mut app = headless_app();
+        app.insert_resource(Metro::new()).add_plugins(plugin);
+        app.update();
+        app
+    }
+
+    fn camera_position(app: &mut App) -> Vec2 {
+        let world = app.world_mut();
+        world
+            .query_filtered::<&Transform, With<Camera2d>>()
+            .single(world)
+            .unwrap()
+            .translation
+            .truncate()
+    }
+
+    /// Puts two fingers down at `from`, and moves them to `to` in the next frame.
+    fn two_finger_gesture(app: &mut App, from: [Vec2; 2], to: [Vec2; 2]) {
+        for (id, position) in from.into_iter().enumerate() {
+            touch(app, id as u64, TouchPhase::Started, position);
+        }
+        app.update();
+
+        for (id, position) in to.into_iter().enumerate() {
+            touch(app, id as u64, TouchPhase::Moved, position);
+        }
+        app.update();
+    }
+
+    #[test]
+    fn pinch_zooms_around_the_fingers() {
+        let mut app = camera_app();
+        let middle = WINDOW_SIZE / 2.;
+
+        two_finger_gesture(
+            &mut app,
+            [middle - Vec2::X * 100., middle + Vec2::X * 100.],
+            [middle - Vec2::X * 150., middle + Vec2::X * 150.],
+        );
+
+        let zoom = app.world().resource::<CameraView>().zoom;
+        assert!((zoom - 1.5).abs() < 1e-4, "zoom is {zoom}");
+        // The fingers are spread around the middle of the window, so that's where the view stays.
+        assert!(camera_position(&mut app).length() < 1e-3);
+    }
+
+    #[test]
+    fn two_finger_drag_pans_the_view() {
+        let mut app = camera_app();
+        app.world_mut().resource_mut::<CameraView>().zoom = 2.;
+        app.update();
+
+        let from = [Vec2::new(300., 300.), Vec2::new(500., 300.)];
+        let delta = Vec2::new(-40., 20.);
+        two_finger_gesture(&mut app, from, from.map(|position| position + delta));
+
+        assert_eq!(app.world().resource::<CameraView>().zoom, 2.);
+        // The map follows the fingers, so the camera moves the other way. The window's y points
+        // down, the world's up.
+        let scale = fit_scale(WINDOW_SIZE, MAP_SIZE) / 2.;
+        let expected = Vec2::new(-delta.x, delta.y) * scale;
+        let position = camera_position(&mut app);
+        assert!(
+            position.distance(expected) < 1e-3,
+            "camera is at {position}, expected {expected}"
+        );
+    }
+}
@@ -0,0 +1,246 @@
+use bevy::prelude::*;
+
+use crate::{
+    AppState, GameState,
+    input::{Action, ActionInput},
+    settings::Preferences,
+};
+
+/// How many in-game minutes pass during one real second.
+pub const MINUTES_PER_SECOND: f32 = 12.;
+pub const MINUTES_PER_DAY: f32 = 24. * 60.;
+pub const DAYS_PER_WEEK: u32 = 7;
+/// The run starts on monday morning, so that the first rush hour comes shortly after.
+const START_MINUTES: f32 = 6. * 60.;
+
+/// The speeds the game can run at, selected with `Action::GAME_SPEEDS`.
+pub const GAME_SPEEDS: [f32; 3] = [1., 2., 4.];
+
+const WEEKDAY_NAMES: [&str; DAYS_PER_WEEK as usize] =
+    ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+
+pub(super) fn plugin(app: &mut App) {
+    app.insert_resource(GameClock::new())
+        .insert_resource(DemandProfiles::default())
+        .insert_resource(GameSpeed(GAME_SPEEDS[0]))
+        .add_systems(OnEnter(AppState::Game), reset_clock)
+        .add_systems(OnExit(AppState::Game), restore_time_speed)
+        .add_systems(Update, tick_clock.run_if(in_state(GameState::Running)))
+        .add_systems(
+            Update,
+            (
+                change_game_speed,
+                apply_game_speed.run_if(resource_changed::<GameSpeed>),
+            )
+                .chain()
+                .run_if(in_state(AppState::Game)),
+        );
+}
+
+/// How many times faster than normal the simulation runs. Applied to `Time<Virtual>`, so everything
+/// that uses `Time` in the game speeds up with it.
+#[derive(Resource)]
+pub struct GameSpeed(pub f32);
+
+#[derive(Resource)]
+pub struct GameClock {
+    /// In-game minutes since midnight of the first day.
+    pub minutes: f32,
+}
+
+impl GameClock {
+    pub fn new() -> Self {
+        GameClock {
+            minutes: START_MINUTES,
+        }
+    }
+
+    /// Zero-based index of the current day since the start of the run.
+    pub fn day(&self) -> u32 {
+        (self.minutes / MINUTES_PER_DAY) as u32
+    }
+
+    /// Zero-based index of the current week since the start of the run.
+    pub fn week(&self) -> u32 {
+        self.day() / DAYS_PER_WEEK
+    }
+
+    /// 0 is monday, 6 is sunday.
+    pub fn weekday(&self) -> u32 {
+        self.day() % DAYS_PER_WEEK
+    }
+
+    pub fn weekday_name(&self) -> &'static str {
+        WEEKDAY_NAMES[self.weekday() as usize]
+    }
+
+    pub fn is_weekend(&self) -> bool {
+        self.weekday() >= 5
+    }
+
+    /// Fractional hour of the current day. From 0.0 to 24.0.
+    pub fn hour(&self) -> f32 {
+        (self.minutes % MINUTES_PER_DAY) / 60.
+    }
+
+    /// Formats the time of day as `HH:MM`.
+    pub fn time_of_day(&self) -> String {
+        let minutes = (self.minutes % MINUTES_PER_DAY) as u32;
+        format!("{:02}:{:02}", minutes / 60, minutes % 60)
+    }
+}
+
+/// What kind of neighbourhood a station is in. Decides when the station is the busiest.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum District {
+    /// People leave for work in the morning.
+    Residential,
+    /// People leave work in the evening.
+    Commercial,
+    /// A bit of both.
+    Mixed,
+}
+
+/// A piecewise linear function of the hour of the day.
+#[derive(Clone)]
+pub struct DemandCurve {
+    /// `(hour, multiplier)` pairs, sorted by hour. Values before the first and after the last
+    /// point are clamped.
+    pub points: Vec<(f32, f32)>,
+}
+
+impl DemandCurve {
+    pub fn new(points: Vec<(f32, f32)>) -> Self {
+        DemandCurve { points }
+    }
+
+    pub fn sample(&self, hour: f32) -> f32 {
+        let (Some(first), Some(last)) = (self.points.first(), self.points.last()) else {
+            return 1.0;
+        };
+
+        if hour <= first.0 {
+            return first.1;
+        }
+        if hour >= last.0 {
+            return last.1;
+        }
+
+        for window in self.points.windows(2) {
+            let [(start_hour, start_value), (end_hour, end_value)] = [window[0], window[1]];
+
+            if hour >= start_hour && hour <= end_hour {
+                let ratio = (hour - start_hour) / (end_hour - start_hour).max(f32::EPSILON);
+                return FloatExt::lerp(start_value, end_value, ratio);
+            }
+        }
+
+        last.1
+    }
+}
+
+/// Multipliers applied to `Station::intensity` depending on the time of day and the district.
+#[derive(Resource, Clone)]
+pub struct DemandProfiles {
+    pub residential: DemandCurve,
+    pub commercial: DemandCurve,
+    pub mixed: DemandCurve,
+    /// Applied on top of the curves on saturdays and sundays.
+    pub weekend_multiplier: f32,
+}
+
+impl Default for DemandProfiles {
+    fn default() -> Self {
+        DemandProfiles {
+            residential: DemandCurve::new(vec![
+                (0., 0.2),
+                (6., 0.6),
+                (8., 2.0),
+                (10., 0.8),
+                (16., 0.6),
+                (18., 0.9),
+                (22., 0.4),
+                (24., 0.2),
+            ]),
+            commercial: DemandCurve::new(vec![
+                (0., 0.1),
+                (7., 0.4),
+                (9., 0.8),
+                (12., 1.0),
+                (17., 2.0),
+                (19., 1.0),
+                (22., 0.3),
+                (24., 0.1),
+            ]),
+            mixed: DemandCurve::new(vec![
+                (0., 0.3),
+                (8., 1.4),
+                (12., 1.0),
+                (18., 1.4),
+                (24., 0.3),
+            ]),
+            weekend_multiplier: 0.5,
+        }
+    }
+}
+
+impl DemandProfiles {
+    pub fn curve(&self, district: District) -> &DemandCurve {
+        match district {
+            District::Residential => &self.residential,
+            District::Commercial => &self.commercial,
+            District::Mixed => &self.mixed,
+        }
+    }
+
+    pub fn multiplier(&self, district: District, clock: &GameClock) -> f32 {
+        let weekend_multiplier = if clock.is_weekend() {
+            self.weekend_multiplier
+        } else {
+            1.0
+        };
+
+        self.curve(district).sample(clock.hour()) * weekend_multiplier
+    }
+}
+
+fn reset_clock(
+    mut clock: ResMut<GameClock>,
+    mut game_speed: ResMut<GameSpeed>,
+    preferences: Res<Preferences>,
+) {
+    *clock = GameClock::new();
+    game_speed.0 = preferences.starting_game_speed();
+}
+
+fn restore_time_speed(mut time: ResMut<Time<Virtual>>) {
+    time.set_relative_speed(1.);
+}
+
+fn change_game_speed(
+    mut commands: Commands,
+    mut game_speed: ResMut<GameSpeed>,
+    input: ActionInput,
+    game_state: Res<State<GameState>>,
+) {
+    for (action, speed) in Action::GAME_SPEEDS.into_iter().zip(GAME_SPEEDS) {
+        if input.just_pressed(action) && game_speed.0 != speed {
+            game_speed.0 = speed;
+        }
+    }
+
+    if input.just_pressed(Action::Pause) {
+        commands.set_state(match game_state.get() {
+            GameState::Running => GameState::Paused,
+            GameState::Paused => GameState::Running,
+        });
+    }
+}
+
+fn apply_game_speed(mut time: ResMut<Time<Virtual>>, game_speed: Res<GameSpeed>) {
+    time.set_relative_speed(game_speed.0);
+}
+
+fn tick_clock(mut clock: ResMut<GameClock>, time: Res<Time>) {
+    clock.minutes += time.delta_secs() * MINUTES_PER_SECOND;
+}
@@ -5,15 +5,42 @@ use super::metro::*;
 pub(super) fn plugin(app: &mut App) {
     app.add_event::<ActiveLinesChanged>()
         .add_event::<LinePathChanged>()
-        .add_event::<LineDragHoversStation>();
+        .add_event::<LineDragHoversStation>()
+        .add_event::<PassengerDelivered>()
+        .add_event::<PassengerAbandoned>()
+        .add_event::<InventoryChanged>()
+        .add_event::<HistoryRequested>()
+        .add_event::<NoticeRequested>();
 }
 
 #[derive(Event)]
 pub struct ActiveLinesChanged;
 
+/// A train or carriage was taken out of, or put back into the inventory.
+#[derive(Event)]
+pub struct InventoryChanged;
+
+/// Undo or redo the last change to the network.
+#[derive(Event, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum HistoryRequested {
+    Undo,
+    Redo,
+}
+
+/// A short message for the player, shown over the map for a moment. For actions that didn't do
+/// anything, or that finished somewhere the player can't see.
+#[derive(Event)]
+pub struct NoticeRequested(pub String);
+
+/// The line being drawn entered or left a station.
 #[derive(Event)]
 pub struct LineDragHoversStation {
     pub station_id: StationId,
+    /// `false` when the drag left the station.
+    pub hovering: bool,
+    /// Whether the line can be connected to the station. Stations that are already on the path
+    /// can't be.
+    pub valid: bool,
 }
 
 #[derive(Event)]
@@ -21,3 +48,20 @@ pub struct LinePathChanged {
     pub line_id: LineId,
     pub new_path: Vec<StationId>,
 }
+
+#[derive(Event)]
+pub struct PassengerDelivered {
+    pub passenger: Passenger,
+    pub station_id: StationId,
+    /// In-game minutes.
+    pub time: f32,
+}
+
+/// The passenger got tired of waiting and left the station.
+#[derive(Event)]
+pub struct PassengerAbandoned {
+    pub passenger: Passenger,
+    pub station_id: StationId,
+    /// In-game minutes.
+    pub time: f32,
+}
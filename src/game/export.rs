@@ -0,0 +1,436 @@
+use std::{
+    fmt::Write as _,
+    fs, io,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use bevy::prelude::*;
+use serde::Serialize;
+
+use crate::{
+    AppState, GameState,
+    input::{Action, ActionInput},
+};
+
+use super::{
+    clock::GameClock,
+    events::NoticeRequested,
+    geometry::ArcLength,
+    metro::{self, LineId, Metro, StationId},
+    satisfaction::Satisfaction,
+    statistics::Statistics,
+};
+
+/// How often (in in-game minutes) the state of the network is sampled.
+const SAMPLE_INTERVAL: f32 = 30.;
+/// Every run gets its own directory in here.
+const EXPORT_DIRECTORY: &str = "exports";
+
+pub(super) fn plugin(app: &mut App) {
+    app.insert_resource(RunRecorder::default())
+        .add_systems(OnEnter(AppState::Game), reset_recorder)
+        .add_systems(
+            OnExit(AppState::Game),
+            export_on_exit.before(metro::reset_metro),
+        )
+        .add_systems(Update, sample_run.run_if(in_state(GameState::Running)))
+        .add_systems(Update, export_on_demand.run_if(in_state(AppState::Game)));
+}
+
+#[derive(Serialize, Clone)]
+pub struct StationSample {
+    pub station_id: StationId,
+    pub waiting: usize,
+}
+
+#[derive(Serialize, Clone)]
+pub struct TrainSample {
+    pub train_index: usize,
+    pub line_id: LineId,
+    pub load: usize,
+    pub capacity: usize,
+}
+
+#[derive(Serialize, Clone)]
+pub struct LineSample {
+    pub line_id: LineId,
+    pub stations: usize,
+    /// In world units.
+    pub length: f32,
+}
+
+/// The state of the network at one point in time.
+#[derive(Serialize, Clone)]
+pub struct Tick {
+    /// In-game minutes.
+    pub minutes: f32,
+    pub day: u32,
+    pub week: u32,
+    pub delivered: u32,
+    pub abandoned: u32,
+    pub stations: Vec<StationSample>,
+    pub trains: Vec<TrainSample>,
+    pub lines: Vec<LineSample>,
+}
+
+#[derive(Serialize)]
+struct WeekRow {
+    week: usize,
+    delivered: u32,
+    abandoned: u32,
+    wait_mean: f32,
+    wait_p50: f32,
+    wait_p90: f32,
+    ride_mean: f32,
+}
+
+#[derive(Serialize)]
+struct StationLayout {
+    station_id: StationId,
+    kind: usize,
+    district: String,
+    position: [f32; 2],
+}
+
+#[derive(Serialize)]
+struct LineLayout {
+    line_id: LineId,
+    path: Vec<StationId>,
+}
+
+#[derive(Serialize)]
+struct TrainLayout {
+    line_id: LineId,
+    locomotives: usize,
+    carriages: usize,
+}
+
+#[derive(Serialize)]
+struct NetworkLayout {
+    stations: Vec<StationLayout>,
+    lines: Vec<LineLayout>,
+    trains: Vec<TrainLayout>,
+}
+
+#[derive(Serialize)]
+struct RunExport<'a> {
+    ticks: &'a [Tick],
+    weeks: Vec<WeekRow>,
+    network: NetworkLayout,
+}
+
+/// Collects the time series that get exported at the end of the run.
+#[derive(Resource, Default)]
+pub struct RunRecorder {
+    pub ticks: Vec<Tick>,
+    last_sample: Option<f32>,
+}
+
+impl RunRecorder {
+    fn sample(&mut self, metro: &Metro, satisfaction: &Satisfaction, clock: &GameClock) {
+        let mut line_ids = metro.lines.keys().copied().collect::<Vec<_>>();
+        line_ids.sort();
+
+        self.ticks.push(Tick {
+            minutes: clock.minutes,
+            day: clock.day(),
+            week: clock.week(),
+            delivered: satisfaction.delivered,
+            abandoned: satisfaction.abandoned,
+            stations: metro
+                .stations
+                .iter()
+                .enumerate()
+                .map(|(station_id, station)| StationSample {
+                    station_id,
+                    waiting: station.passengers.len(),
+                })
+                .collect(),
+            trains: metro
+                .trains
+                .iter()
+                .enumerate()
+                .map(|(train_index, train)| TrainSample {
+                    train_index,
+                    line_id: train.line_id,
+                    load: train.passengers.len(),
+                    capacity: train.capacity(),
+                })
+                .collect(),
+            lines: line_ids
+                .into_iter()
+                .map(|line_id| LineSample {
+                    line_id,
+                    stations: metro.lines[&line_id].len(),
+                    length: line_length(metro, line_id),
+                })
+                .collect(),
+        });
+        self.last_sample = Some(clock.minutes);
+    }
+}
+
+/// The length trains actually travel along the line, lane offsets and corners included.
+fn line_length(metro: &Metro, line_id: LineId) -> f32 {
+    ArcLength::new(metro.line_geometry(line_id)).length()
+}
+
+fn reset_recorder(mut recorder: ResMut<RunRecorder>) {
+    *recorder = RunRecorder::default();
+}
+
+fn sample_run(
+    mut recorder: ResMut<RunRecorder>,
+    metro: Res<Metro>,
+    satisfaction: Res<Satisfaction>,
+    clock: Res<GameClock>,
+) {
+    if recorder
+        .last_sample
+        .is_some_and(|last_sample| clock.minutes - last_sample < SAMPLE_INTERVAL)
+    {
+        return;
+    }
+
+    recorder.sample(&metro, &satisfaction, &clock);
+}
+
+fn export_on_demand(
+    input: ActionInput,
+    recorder: Res<RunRecorder>,
+    metro: Res<Metro>,
+    statistics: Res<Statistics>,
+    mut notice_requested_events: EventWriter<NoticeRequested>,
+) {
+    if !input.just_pressed(Action::Export) {
+        return;
+    }
+
+    let message = match export(&recorder, &metro, &statistics) {
+        Ok(directory) => format!("Exported to {}", directory.display()),
+        Err(err) => format!("Couldn't export: {err}"),
+    };
+    notice_requested_events.write(NoticeRequested(message));
+}
+
+/// Nobody is left to see a notice by now, so the result only goes to the log.
+fn export_on_exit(recorder: Res<RunRecorder>, metro: Res<Metro>, statistics: Res<Statistics>) {
+    let _ = export(&recorder, &metro, &statistics);
+}
+
+/// Writes the run into a new directory, and returns it.
+fn export(recorder: &RunRecorder, metro: &Metro, statistics: &Statistics) -> io::Result<PathBuf> {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis())
+        .unwrap_or_default();
+    let directory = unused_directory(&format!("run-{timestamp}"));
+
+    match write_run(&directory, recorder, metro, statistics) {
+        Ok(()) => {
+            info!("exported run statistics to {}", directory.display());
+            Ok(directory)
+        }
+        Err(err) => {
+            warn!("couldn't export run statistics: {err}");
+            Err(err)
+        }
+    }
+}
+
+/// A directory named `name` in the export directory, with a sequence number appended if
+/// it's already taken so two exports never overwrite each other.
+fn unused_directory(name: &str) -> PathBuf {
+    let base = PathBuf::from(EXPORT_DIRECTORY);
+    let mut directory = base.join(name);
+    let mut sequence = 1;
+
+    while directory.exists() {
+        directory = base.join(format!("{name}-{sequence}"));
+        sequence += 1;
+    }
+
+    directory
+}
+
+fn write_run(
+    directory: &Path,
+    recorder: &RunRecorder,
+    metro: &Metro,
+    statistics: &Statistics,
+) -> io::Result<()> {
+    fs::create_dir_all(directory)?;
+
+    let weeks = week_rows(statistics);
+
+    fs::write(directory.join("ticks.csv"), ticks_csv(&recorder.ticks))?;
+    fs::write(
+        directory.join("tick_stations.csv"),
+        tick_stations_csv(&recorder.ticks),
+    )?;
+    fs::write(
+        directory.join("tick_trains.csv"),
+        tick_trains_csv(&recorder.ticks),
+    )?;
+    fs::write(
+        directory.join("tick_lines.csv"),
+        tick_lines_csv(&recorder.ticks),
+    )?;
+    fs::write(directory.join("weeks.csv"), weeks_csv(&weeks))?;
+
+    let run = RunExport {
+        ticks: &recorder.ticks,
+        weeks,
+        network: network_layout(metro),
+    };
+    let json = serde_json::to_string_pretty(&run).map_err(io::Error::other)?;
+    fs::write(directory.join("run.json"), json)?;
+
+    Ok(())
+}
+
+fn week_rows(statistics: &Statistics) -> Vec<WeekRow> {
+    statistics
+        .weeks
+        .iter()
+        .enumerate()
+        .map(|(week, aggregate)| {
+            let wait_time = aggregate.wait_time();
+
+            WeekRow {
+                week,
+                delivered: aggregate.completed,
+                abandoned: aggregate.abandoned,
+                wait_mean: wait_time.mean,
+                wait_p50: wait_time.p50,
+                wait_p90: wait_time.p90,
+                ride_mean: aggregate.travel_time().mean,
+            }
+        })
+        .collect()
+}
+
+fn network_layout(metro: &Metro) -> NetworkLayout {
+    let mut lines = metro
+        .lines
+        .iter()
+        .map(|(line_id, path)| LineLayout {
+            line_id: *line_id,
+            path: path.clone(),
+        })
+        .collect::<Vec<_>>();
+    lines.sort_by_key(|line| line.line_id);
+
+    NetworkLayout {
+        stations: metro
+            .stations
+            .iter()
+            .enumerate()
+            .map(|(station_id, station)| StationLayout {
+                station_id,
+                kind: station.kind,
+                district: format!("{:?}", station.district),
+                position: station.position.to_array(),
+            })
+            .collect(),
+        lines,
+        trains: metro
+            .trains
+            .iter()
+            .map(|train| TrainLayout {
+                line_id: train.line_id,
+                locomotives: train.locomotive_count,
+                carriages: train.carriage_count,
+            })
+            .collect(),
+    }
+}
+
+fn ticks_csv(ticks: &[Tick]) -> String {
+    let mut csv = String::from("minutes,day,week,delivered,abandoned,waiting\n");
+
+    for tick in ticks {
+        let waiting = tick
+            .stations
+            .iter()
+            .map(|station| station.waiting)
+            .sum::<usize>();
+
+        let _ = writeln!(
+            csv,
+            "{},{},{},{},{},{}",
+            tick.minutes, tick.day, tick.week, tick.delivered, tick.abandoned, waiting
+        );
+    }
+
+    csv
+}
+
+fn tick_stations_csv(ticks: &[Tick]) -> String {
+    let mut csv = String::from("minutes,station_id,waiting\n");
+
+    for tick in ticks {
+        for station in &tick.stations {
+            let _ = writeln!(
+                csv,
+                "{},{},{}",
+                tick.minutes, station.station_id, station.waiting
+            );
+        }
+    }
+
+    csv
+}
+
+fn tick_trains_csv(ticks: &[Tick]) -> String {
+    let mut csv = String::from("minutes,train_index,line_id,load,capacity\n");
+
+    for tick in ticks {
+        for train in &tick.trains {
+            let _ = writeln!(
+                csv,
+                "{},{},{},{},{}",
+                tick.minutes, train.train_index, train.line_id, train.load, train.capacity
+            );
+        }
+    }
+
+    csv
+}
+
+fn tick_lines_csv(ticks: &[Tick]) -> String {
+    let mut csv = String::from("minutes,line_id,stations,length\n");
+
+    for tick in ticks {
+        for line in &tick.lines {
+            let _ = writeln!(
+                csv,
+                "{},{},{},{}",
+                tick.minutes, line.line_id, line.stations, line.length
+            );
+        }
+    }
+
+    csv
+}
+
+fn weeks_csv(weeks: &[WeekRow]) -> String {
+    let mut csv = String::from("week,delivered,abandoned,wait_mean,wait_p50,wait_p90,ride_mean\n");
+
+    for week in weeks {
+        let _ = writeln!(
+            csv,
+            "{},{},{},{},{},{},{}",
+            week.week,
+            week.delivered,
+            week.abandoned,
+            week.wait_mean,
+            week.wait_p50,
+            week.wait_p90,
+            week.ride_mean
+        );
+    }
+
+    csv
+}
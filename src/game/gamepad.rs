@@ -0,0 +1,231 @@
+use bevy::{
+    asset::uuid::Uuid,
+    picking::pointer::{Location, PointerAction, PointerId, PointerInput},
+    prelude::*,
+    render::camera::NormalizedRenderTarget,
+    window::{PrimaryWindow, WindowRef},
+};
+
+use crate::{
+    AppState,
+    input::{Action, ActionInput},
+    style::{ThemeColor, ThemedBackground, ThemedBorder, place_node},
+};
+
+use super::GameComponent;
+
+/// The pointer moved by the left stick. Picking treats it like the mouse, so the drag handlers in
+/// `lines` and `ui` work with it as they are.
+const GAMEPAD_POINTER: PointerId =
+    PointerId::Custom(Uuid::from_u128(0x5f0c_2a8e_6d3b_4b7a_9e41_c2d8_0a6f_13b5));
+/// Logical pixels per real second at full tilt.
+const CURSOR_SPEED: f32 = 700.;
+const CURSOR_SIZE: f32 = 16.;
+
+pub(super) fn plugin(app: &mut App) {
+    app.add_systems(OnEnter(AppState::Game), setup_gamepad_cursor)
+        .add_systems(
+            Update,
+            (move_gamepad_cursor, press_gamepad_cursor)
+                .chain()
+                .run_if(in_state(AppState::Game)),
+        );
+}
+
+/// Where the gamepad cursor is on the window. Hidden until the stick is first moved.
+#[derive(Component)]
+struct GamepadCursor {
+    position: Option<Vec2>,
+}
+
+fn setup_gamepad_cursor(mut commands: Commands) {
+    commands.spawn((GameComponent, GAMEPAD_POINTER));
+
+    commands.spawn((
+        GameComponent,
+        GamepadCursor { position: None },
+        Node {
+            display: Display::None,
+            position_type: PositionType::Absolute,
+            width: Val::Px(CURSOR_SIZE),
+            height: Val::Px(CURSOR_SIZE),
+            border: UiRect::all(Val::Px(2.)),
+            ..default()
+        },
+        ThemedBackground::new(ThemeColor::OnBackground).with_alpha(0.6),
+        ThemedBorder(ThemeColor::Background),
+        BorderRadius::MAX,
+        // Above everything else, including the tooltips.
+        GlobalZIndex(i32::MAX),
+        Pickable::IGNORE,
+    ));
+}
+
+fn pointer_location(window: Entity, position: Vec2) -> Location {
+    Location {
+        target: NormalizedRenderTarget::Window(WindowRef::Entity(window).normalize(None).unwrap()),
+        position,
+    }
+}
+
+fn move_gamepad_cursor(
+    cursor_q: Single<(&mut GamepadCursor, &mut Node)>,
+    window_q: Single<(Entity, &Window), With<PrimaryWindow>>,
+    gamepads: Query<&Gamepad>,
+    mut pointer_events: EventWriter<PointerInput>,
+    time: Res<Time<Real>>,
+    ui_scale: Res<UiScale>,
+) {
+    let stick = gamepads
+        .iter()
+        .map(|gamepad| gamepad.left_stick())
+        .fold(Vec2::ZERO, |sum, stick| sum + stick)
+        .clamp_length_max(1.);
+    if stick == Vec2::ZERO {
+        return;
+    }
+
+    let (mut cursor, mut node) = cursor_q.into_inner();
+    let (window_entity, window) = *window_q;
+
+    let previous = cursor.position.unwrap_or(window.size() / 2.);
+    // The stick's y points up, the window's down.
+    let position = (previous + Vec2::new(stick.x, -stick.y) * CURSOR_SPEED * time.delta_secs())
+        .clamp(Vec2::ZERO, window.size());
+    cursor.position = Some(position);
+
+    node.display = Display::Flex;
+    place_node(
+        &mut node,
+        position,
+        Vec2::splat(-CURSOR_SIZE / 2.),
+        &ui_scale,
+    );
+
+    pointer_events.write(PointerInput::new(
+        GAMEPAD_POINTER,
+        pointer_location(window_entity, position),
+        PointerAction::Move {
+            delta: position - previous,
+        },
+    ));
+}
+
+fn press_gamepad_cursor(
+    cursor: Single<&GamepadCursor>,
+    window: Single<Entity, With<PrimaryWindow>>,
+    input: ActionInput,
+    mut pointer_events: EventWriter<PointerInput>,
+) {
+    let Some(position) = cursor.position else {
+        return;
+    };
+
+    let location = pointer_location(*window, position);
+
+    if input.just_pressed(Action::Click) {
+        pointer_events.write(PointerInput::new(
+            GAMEPAD_POINTER,
+            location.clone(),
+            PointerAction::Press(PointerButton::Primary),
+        ));
+    }
+    if input.just_released(Action::Click) {
+        pointer_events.write(PointerInput::new(
+            GAMEPAD_POINTER,
+            location,
+            PointerAction::Release(PointerButton::Primary),
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        GameState,
+        game::clock,
+        settings::Preferences,
+        testing::{
+            headless_app, set_gamepad_axis, set_gamepad_button, spawn_gamepad, tap_gamepad_button,
+        },
+    };
+
+    fn gamepad_app() -> (App, Entity) {
+        let mut app = headless_app();
+        app.init_resource::<Preferences>()
+            .add_plugins((plugin, clock::plugin));
+        let gamepad = spawn_gamepad(&mut app);
+        app.update();
+
+        (app, gamepad)
+    }
+
+    /// What the gamepad cursor did this frame.
+    fn cursor_actions(app: &App) -> Vec<PointerAction> {
+        app.world()
+            .resource::<Events<PointerInput>>()
+            .iter_current_update_events()
+            .filter(|event| event.pointer_id == GAMEPAD_POINTER)
+            .map(|event| event.action)
+            .collect()
+    }
+
+    #[test]
+    fn stick_and_south_button_drive_the_cursor() {
+        let (mut app, gamepad) = gamepad_app();
+
+        set_gamepad_axis(&mut app, gamepad, GamepadAxis::LeftStickX, 1.);
+        app.update();
+        assert!(
+            cursor_actions(&app)
+                .iter()
+                .any(|action| matches!(action, PointerAction::Move { delta } if delta.x > 0.))
+        );
+
+        set_gamepad_axis(&mut app, gamepad, GamepadAxis::LeftStickX, 0.);
+        set_gamepad_button(&mut app, gamepad, GamepadButton::South, 1.);
+        app.update();
+        assert!(matches!(
+            cursor_actions(&app)[..],
+            [PointerAction::Press(PointerButton::Primary)]
+        ));
+
+        set_gamepad_button(&mut app, gamepad, GamepadButton::South, 0.);
+        app.update();
+        assert!(matches!(
+            cursor_actions(&app)[..],
+            [PointerAction::Release(PointerButton::Primary)]
+        ));
+    }
+
+    #[test]
+    fn start_toggles_pause() {
+        let (mut app, gamepad) = gamepad_app();
+        let game_state = |app: &App| app.world().resource::<State<GameState>>().get().clone();
+        assert_eq!(game_state(&app), GameState::Running);
+
+        tap_gamepad_button(&mut app, gamepad, GamepadButton::Start);
+        assert_eq!(game_state(&app), GameState::Paused);
+
+        tap_gamepad_button(&mut app, gamepad, GamepadButton::Start);
+        assert_eq!(game_state(&app), GameState::Running);
+    }
+    #[test]
+    fn cursor_follows_the_pointer_at_any_ui_scale() {
+        let (mut app, gamepad) = gamepad_app();
+        app.insert_resource(UiScale(2.));
+
+        set_gamepad_axis(&mut app, gamepad, GamepadAxis::LeftStickX, 1.);
+        app.update();
+
+        let world = app.world_mut();
+        let (cursor, node) = world
+            .query::<(&GamepadCursor, &Node)>()
+            .single(world)
+            .unwrap();
+        let position = cursor.position.unwrap();
+        assert_eq!(node.left, Val::Px(position.x / 2. - CURSOR_SIZE / 2.));
+        assert_eq!(node.top, Val::Px(position.y / 2. - CURSOR_SIZE / 2.));
+    }
+}
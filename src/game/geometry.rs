@@ -0,0 +1,229 @@
+use bevy::math::{FloatExt, Vec2};
+
+/// Distance from the corner at which the rounded part of an octilinear bend starts.
+const CORNER_RADIUS: f32 = 6.;
+/// How many points approximate the rounded part of a bend.
+const CORNER_SEGMENTS: usize = 6;
+/// Segments closer than this are considered to be on top of each other.
+const OVERLAP_TOLERANCE: f32 = 0.5;
+
+/// Which leg of an octilinear connection comes first, when going from the first station to the
+/// second one.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Bend {
+    #[default]
+    StraightFirst,
+    DiagonalFirst,
+}
+
+/// Connects `a` and `b` with one horizontal or vertical leg and one diagonal leg, like on metro
+/// diagrams. Returns `[a, corner, b]`, or `[a, b]` if they're already on a 45° multiple.
+pub fn octilinear_route(a: Vec2, b: Vec2, bend: Bend) -> Vec<Vec2> {
+    let delta = b - a;
+    let (dx, dy) = (delta.x.abs(), delta.y.abs());
+    let diagonal = dx.min(dy);
+
+    if diagonal < OVERLAP_TOLERANCE || (dx - dy).abs() < OVERLAP_TOLERANCE {
+        return vec![a, b];
+    }
+
+    let sign = delta.signum();
+    let diagonal_leg = sign * diagonal;
+    let straight_leg = if dx > dy {
+        Vec2::new(sign.x * (dx - dy), 0.)
+    } else {
+        Vec2::new(0., sign.y * (dy - dx))
+    };
+
+    let corner = match bend {
+        Bend::StraightFirst => a + straight_leg,
+        Bend::DiagonalFirst => a + diagonal_leg,
+    };
+
+    vec![a, corner, b]
+}
+
+/// Points `direction` to the right, or up if it's vertical, so that both directions of a segment
+/// agree on which side is left.
+pub fn canonical_direction(direction: Vec2) -> Vec2 {
+    if direction.x > f32::EPSILON || (direction.x.abs() <= f32::EPSILON && direction.y > 0.) {
+        direction
+    } else {
+        -direction
+    }
+}
+
+/// Moves the polyline `offset` units to its left, keeping the segments parallel to the original
+/// ones.
+pub fn offset_polyline(points: &[Vec2], offset: f32) -> Vec<Vec2> {
+    if offset == 0. || points.len() < 2 {
+        return points.to_vec();
+    }
+
+    let normals = points
+        .windows(2)
+        .map(|pair| (pair[1] - pair[0]).normalize_or_zero().perp())
+        .collect::<Vec<_>>();
+
+    (0..points.len())
+        .map(|i| {
+            let incoming = normals[i.saturating_sub(1)];
+            let outgoing = normals[i.min(normals.len() - 1)];
+
+            let miter = (incoming + outgoing).normalize_or_zero();
+            let miter_scale = 1. / miter.dot(outgoing).max(0.5);
+
+            points[i] + miter * offset * miter_scale
+        })
+        .collect()
+}
+
+/// Replaces every inner point of the polyline with a smooth curve.
+pub fn smooth_corners(points: &[Vec2]) -> Vec<Vec2> {
+    if points.len() < 3 {
+        return points.to_vec();
+    }
+
+    let mut smoothed = vec![points[0]];
+
+    for i in 1..points.len() - 1 {
+        let (previous, corner, next) = (points[i - 1], points[i], points[i + 1]);
+
+        let incoming = corner - previous;
+        let outgoing = next - corner;
+        // Leave some room for the neighbouring corners.
+        let radius = CORNER_RADIUS
+            .min(incoming.length() / 2.)
+            .min(outgoing.length() / 2.);
+
+        let curve_start = corner - incoming.normalize_or_zero() * radius;
+        let curve_end = corner + outgoing.normalize_or_zero() * radius;
+
+        // A quadratic bezier curve with the corner as the control point.
+        for step in 0..=CORNER_SEGMENTS {
+            let t = step as f32 / CORNER_SEGMENTS as f32;
+            let point =
+                curve_start * (1. - t) * (1. - t) + corner * 2. * (1. - t) * t + curve_end * t * t;
+            smoothed.push(point);
+        }
+    }
+
+    smoothed.push(points[points.len() - 1]);
+    smoothed.dedup_by(|a, b| a.distance_squared(*b) < f32::EPSILON);
+
+    smoothed
+}
+
+/// A polyline parameterised by the distance travelled along it.
+pub struct ArcLength {
+    points: Vec<Vec2>,
+    /// Distance from the start of the polyline to each of its points.
+    cumulative: Vec<f32>,
+}
+
+impl ArcLength {
+    pub fn new(points: Vec<Vec2>) -> Self {
+        let mut cumulative = Vec::with_capacity(points.len());
+        let mut length = 0.;
+
+        for (i, point) in points.iter().enumerate() {
+            if i > 0 {
+                length += points[i - 1].distance(*point);
+            }
+            cumulative.push(length);
+        }
+
+        ArcLength { points, cumulative }
+    }
+
+    pub fn length(&self) -> f32 {
+        self.cumulative.last().copied().unwrap_or(0.)
+    }
+
+    /// The point `distance` units along the polyline, and the direction of the polyline at it.
+    pub fn sample(&self, distance: f32) -> (Vec2, Vec2) {
+        match self.points[..] {
+            [] => return (Vec2::ZERO, Vec2::X),
+            [point] => return (point, Vec2::X),
+            _ => {}
+        }
+
+        let distance = distance.clamp(0., self.length());
+        // The segment that contains `distance`.
+        let end = self
+            .cumulative
+            .partition_point(|length| *length < distance)
+            .clamp(1, self.points.len() - 1);
+        let start = end - 1;
+
+        let segment_length = self.cumulative[end] - self.cumulative[start];
+        let direction = (self.points[end] - self.points[start]).normalize_or_zero();
+        let t = if segment_length > 0. {
+            (distance - self.cumulative[start]) / segment_length
+        } else {
+            0.
+        };
+
+        (self.points[start].lerp(self.points[end], t), direction)
+    }
+
+    /// The distance along the polyline of the point closest to `point`, and how far away that
+    /// point is.
+    pub fn project(&self, point: Vec2) -> (f32, f32) {
+        let mut closest = (0., f32::INFINITY);
+
+        for i in 1..self.points.len() {
+            let (start, end) = (self.points[i - 1], self.points[i]);
+            let segment = end - start;
+            let t = if segment.length_squared() > 0. {
+                ((point - start).dot(segment) / segment.length_squared()).clamp(0., 1.)
+            } else {
+                0.
+            };
+
+            let distance = point.distance(start + segment * t);
+            if distance < closest.1 {
+                let distance_along = FloatExt::lerp(self.cumulative[i - 1], self.cumulative[i], t);
+                closest = (distance_along, distance);
+            }
+        }
+
+        closest
+    }
+}
+
+/// Total length along which the two polylines run on top of each other.
+pub fn overlap_length(a: &[Vec2], b: &[Vec2]) -> f32 {
+    let mut overlap = 0.;
+
+    for segment_a in a.windows(2) {
+        for segment_b in b.windows(2) {
+            overlap += segment_overlap((segment_a[0], segment_a[1]), (segment_b[0], segment_b[1]));
+        }
+    }
+
+    overlap
+}
+
+fn segment_overlap(a: (Vec2, Vec2), b: (Vec2, Vec2)) -> f32 {
+    let direction = (a.1 - a.0).normalize_or_zero();
+    let other_direction = (b.1 - b.0).normalize_or_zero();
+
+    // Octilinear segments are either parallel, or at least 45° apart, so this can be generous.
+    let parallel = direction.perp_dot(other_direction).abs() < 0.1;
+    let collinear = direction.perp_dot(b.0 - a.0).abs() < OVERLAP_TOLERANCE;
+
+    if !parallel || !collinear {
+        return 0.;
+    }
+
+    // Project `b` onto `a`, which then spans from 0 to its length.
+    let project = |point: Vec2| direction.dot(point - a.0);
+    let a_length = a.0.distance(a.1);
+    let (b_start, b_end) = {
+        let (start, end) = (project(b.0), project(b.1));
+        (start.min(end), start.max(end))
+    };
+
+    (a_length.min(b_end) - b_start.max(0.)).max(0.)
+}
@@ -0,0 +1,332 @@
+use bevy::prelude::*;
+
+use crate::{
+    AppState,
+    input::{Action, ActionInput},
+};
+
+use super::{
+    clock::GameClock,
+    events::{ActiveLinesChanged, HistoryRequested, InventoryChanged, NoticeRequested},
+    metro::{LineId, LinePoint, Metro, MetroResources, StationId, TrainId},
+};
+
+/// How many actions can be undone.
+const HISTORY_LIMIT: usize = 100;
+
+pub(super) fn plugin(app: &mut App) {
+    app.init_resource::<History>()
+        .add_systems(OnEnter(AppState::Game), reset_history)
+        .add_systems(
+            Update,
+            (request_from_input, apply_history_requests)
+                .chain()
+                .run_if(in_state(AppState::Game)),
+        );
+}
+
+/// A reversible change to the network.
+#[derive(Clone)]
+pub enum NetworkEdit {
+    /// An empty path means that the line isn't on the map.
+    SetLinePath {
+        line_id: LineId,
+        before: Vec<StationId>,
+        after: Vec<StationId>,
+    },
+    PlaceTrain {
+        train_id: TrainId,
+        point: LinePoint,
+        carriages: usize,
+    },
+    /// Puts the train back into the inventory. `point` is where it was taken from.
+    RemoveTrain {
+        train_id: TrainId,
+        point: LinePoint,
+        carriages: usize,
+    },
+    AddCarriage {
+        train_id: TrainId,
+    },
+    RemoveCarriage {
+        train_id: TrainId,
+    },
+}
+
+impl NetworkEdit {
+    fn inverse(&self) -> NetworkEdit {
+        match self.clone() {
+            NetworkEdit::SetLinePath {
+                line_id,
+                before,
+                after,
+            } => NetworkEdit::SetLinePath {
+                line_id,
+                before: after,
+                after: before,
+            },
+            NetworkEdit::PlaceTrain {
+                train_id,
+                point,
+                carriages,
+            } => NetworkEdit::RemoveTrain {
+                train_id,
+                point,
+                carriages,
+            },
+            NetworkEdit::RemoveTrain {
+                train_id,
+                point,
+                carriages,
+            } => NetworkEdit::PlaceTrain {
+                train_id,
+                point,
+                carriages,
+            },
+            NetworkEdit::AddCarriage { train_id } => NetworkEdit::RemoveCarriage { train_id },
+            NetworkEdit::RemoveCarriage { train_id } => NetworkEdit::AddCarriage { train_id },
+        }
+    }
+
+    /// Applies the edit, unless it contradicts the current state of the network, e.g. the line has
+    /// been changed since, or there are no trains left in the inventory. Returns whether it was
+    /// applied.
+    fn apply(&self, metro: &mut Metro, metro_resources: &MetroResources, now: f32) -> bool {
+        match self {
+            NetworkEdit::SetLinePath {
+                line_id,
+                before,
+                after,
+            } => {
+                let current = metro.lines.get(line_id).cloned().unwrap_or_default();
+                if current != *before {
+                    return false;
+                }
+
+                if after.is_empty() {
+                    metro.remove_line(*line_id, now);
+                } else {
+                    metro.set_line_path(*line_id, after.clone());
+                }
+            }
+            NetworkEdit::PlaceTrain {
+                train_id,
+                point,
+                carriages,
+            } => {
+                if metro.train_index(*train_id).is_some()
+                    || !metro.has_segment(point.line_id, point.from, point.to)
+                    || metro_resources.available_trains(metro) == 0
+                    || metro_resources.available_carriages(metro) < *carriages
+                {
+                    return false;
+                }
+
+                metro.place_train(*point, *train_id);
+                if let Some(train) = metro.trains.last_mut() {
+                    train.carriage_count = *carriages;
+                }
+            }
+            NetworkEdit::RemoveTrain { train_id, .. } => {
+                let Some(train_index) = metro.train_index(*train_id) else {
+                    return false;
+                };
+
+                metro.remove_train(train_index, now);
+            }
+            NetworkEdit::AddCarriage { train_id } => {
+                let Some(train_index) = metro.train_index(*train_id) else {
+                    return false;
+                };
+                if metro_resources.available_carriages(metro) == 0 {
+                    return false;
+                }
+
+                metro.trains[train_index].carriage_count += 1;
+            }
+            NetworkEdit::RemoveCarriage { train_id } => {
+                let Some(train) = metro
+                    .train_index(*train_id)
+                    .map(|train_index| &mut metro.trains[train_index])
+                else {
+                    return false;
+                };
+                if train.carriage_count == 0 {
+                    return false;
+                }
+
+                train.carriage_count -= 1;
+            }
+        }
+
+        true
+    }
+}
+
+/// Applies all the edits in order. If one of them can't be applied, the ones before it are
+/// reverted, so that the action is applied either fully or not at all.
+fn apply_action(
+    edits: &[NetworkEdit],
+    metro: &mut Metro,
+    metro_resources: &MetroResources,
+    now: f32,
+) -> bool {
+    for (i, edit) in edits.iter().enumerate() {
+        if edit.apply(metro, metro_resources, now) {
+            continue;
+        }
+
+        for applied in edits[..i].iter().rev() {
+            applied.inverse().apply(metro, metro_resources, now);
+        }
+        return false;
+    }
+
+    true
+}
+
+/// Removing a line takes its trains off it as well. Those removals are added to the action as
+/// edits of their own, right before the line is removed, so that reverting the action puts the
+/// trains back where they were.
+fn with_train_removals(edits: &[NetworkEdit], metro: &Metro) -> Vec<NetworkEdit> {
+    let mut expanded = Vec::with_capacity(edits.len());
+
+    for edit in edits {
+        if let NetworkEdit::SetLinePath { line_id, after, .. } = edit
+            && after.is_empty()
+        {
+            for train in metro
+                .trains
+                .iter()
+                .filter(|train| train.line_id == *line_id)
+            {
+                let already_removed = expanded.iter().any(|edit| {
+                    matches!(edit, NetworkEdit::RemoveTrain { train_id, .. } if *train_id == train.id)
+                });
+                if already_removed {
+                    continue;
+                }
+
+                expanded.push(NetworkEdit::RemoveTrain {
+                    train_id: train.id,
+                    point: metro.train_point(train),
+                    carriages: train.carriage_count,
+                });
+            }
+        }
+
+        expanded.push(edit.clone());
+    }
+
+    expanded
+}
+
+/// The committed changes to the network. Every entry is one player action, made of one or more
+/// edits.
+#[derive(Resource, Default)]
+pub struct History {
+    undo: Vec<Vec<NetworkEdit>>,
+    redo: Vec<Vec<NetworkEdit>>,
+}
+
+impl History {
+    pub fn record(&mut self, edits: Vec<NetworkEdit>) {
+        if edits.is_empty() {
+            return;
+        }
+
+        self.undo.push(edits);
+        if self.undo.len() > HISTORY_LIMIT {
+            self.undo.remove(0);
+        }
+        self.redo.clear();
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+}
+
+fn reset_history(mut history: ResMut<History>) {
+    *history = History::default();
+}
+
+fn request_from_input(mut history_events: EventWriter<HistoryRequested>, input: ActionInput) {
+    if input.just_pressed(Action::Undo) {
+        history_events.write(HistoryRequested::Undo);
+    }
+    if input.just_pressed(Action::Redo) {
+        history_events.write(HistoryRequested::Redo);
+    }
+}
+
+fn apply_history_requests(
+    mut history_events: EventReader<HistoryRequested>,
+    mut active_lines_changed_events: EventWriter<ActiveLinesChanged>,
+    mut inventory_changed_events: EventWriter<InventoryChanged>,
+    mut notice_requested_events: EventWriter<NoticeRequested>,
+    mut history: ResMut<History>,
+    mut metro: ResMut<Metro>,
+    metro_resources: Res<MetroResources>,
+    clock: Res<GameClock>,
+) {
+    for event in history_events.read() {
+        let applied = match event {
+            HistoryRequested::Undo => {
+                let Some(edits) = history.undo.pop() else {
+                    continue;
+                };
+
+                let inverse = edits
+                    .iter()
+                    .rev()
+                    .map(NetworkEdit::inverse)
+                    .collect::<Vec<_>>();
+                let inverse = with_train_removals(&inverse, &metro);
+
+                if apply_action(&inverse, &mut metro, &metro_resources, clock.minutes) {
+                    history
+                        .redo
+                        .push(inverse.iter().rev().map(NetworkEdit::inverse).collect());
+                    true
+                } else {
+                    history.undo.push(edits);
+                    false
+                }
+            }
+            HistoryRequested::Redo => {
+                let Some(edits) = history.redo.pop() else {
+                    continue;
+                };
+
+                let expanded = with_train_removals(&edits, &metro);
+
+                if apply_action(&expanded, &mut metro, &metro_resources, clock.minutes) {
+                    history.undo.push(expanded);
+                    true
+                } else {
+                    history.redo.push(edits);
+                    false
+                }
+            }
+        };
+
+        if !applied {
+            let action = match event {
+                HistoryRequested::Undo => "undo",
+                HistoryRequested::Redo => "redo",
+            };
+            notice_requested_events.write(NoticeRequested(format!(
+                "Can't {action}, the network has changed since"
+            )));
+            continue;
+        }
+
+        active_lines_changed_events.write(ActiveLinesChanged);
+        inventory_changed_events.write(InventoryChanged);
+    }
+}
@@ -2,21 +2,45 @@ use bevy::{
     color::palettes::css::PURPLE, ecs::relationship::RelationshipSourceCollection, prelude::*,
 };
 
-use crate::game::lines_visual::MetroLineVisualBundle;
+use crate::{
+    AppState,
+    game::lines_visual::MetroLineVisualBundle,
+    input::{Action, ActionInput},
+    settings::Preferences,
+    style::Theme,
+};
 
 use super::{
-    events::LinePathChanged,
+    GameComponent,
+    events::{LineDragHoversStation, LinePathChanged},
+    lines_keyboard,
     lines_visual::MetroLineVisual,
-    metro::{Connection, LINE_COLORS, LineId, Metro, MetroResources, Station, StationId},
+    metro::{Connection, LineId, Metro, MetroResources, Station, StationId},
 };
 
 pub(super) fn plugin(app: &mut App) {
     app.add_observer(on_line_handle_spawned)
-        .insert_resource(LineDragState::None);
+        .insert_resource(LineDragState::None)
+        .init_resource::<LineDragHover>()
+        .add_systems(OnEnter(AppState::Game), reset_line_drag)
+        .add_systems(
+            Update,
+            cancel_line_drag
+                .after(lines_keyboard::build_line)
+                .run_if(in_state(AppState::Game)),
+        );
 }
 
+/// The station the line being drawn is over, and whether it can be connected to it.
+#[derive(Resource, Default)]
+struct LineDragHover(Option<(StationId, bool)>);
+
+/// A faded segment between the station the line would be connected to and the previous one.
+#[derive(Component)]
+struct LineDragHoverGhost;
+
 #[derive(Resource)]
-enum LineDragState {
+pub(super) enum LineDragState {
     None,
     New {
         path: Vec<PathNode>,
@@ -37,7 +61,14 @@ enum LineDragState {
     // line_id: LineId,
 }
 
-struct PathNode {
+impl LineDragState {
+    /// Whether a line is being drawn with the mouse or a finger.
+    pub(super) fn is_active(&self) -> bool {
+        !matches!(self, LineDragState::None)
+    }
+}
+
+pub(super) struct PathNode {
     start_station_id: StationId,
     end_station_id: Option<StationId>,
     line_entity: Entity,
@@ -83,11 +114,18 @@ fn on_drag_start(
     handle_q: Query<&LineDragHandle>,
     lines_q: Query<(Entity, &MetroLine)>,
     camera_transform_q: Single<(&Camera, &GlobalTransform)>,
+    touches: Res<Touches>,
 
     mut line_drag_state: ResMut<LineDragState>,
     metro: Res<Metro>,
     metro_resources: Res<MetroResources>,
+    preferences: Res<Preferences>,
 ) {
+    // The middle button and a second finger pan the view instead, see `camera.rs`.
+    if trigger.button != PointerButton::Primary || touches.iter().nth(1).is_some() {
+        return;
+    }
+
     let (camera, camera_transform) = *camera_transform_q;
 
     let drag_position =
@@ -120,10 +158,11 @@ fn on_drag_start(
 
             let new_line_entity = commands
                 .spawn((
+                    GameComponent,
                     MetroLineVisualBundle::new(
                         station.position,
                         drag_position,
-                        LINE_COLORS[new_line_id].into(),
+                        preferences.line_color(new_line_id).into(),
                     ),
                     MetroLine {
                         start_station_id: *station_id,
@@ -162,13 +201,23 @@ fn on_drag(
     stations_q: Query<&StationLineDragTarget>,
     camera_transform_q: Single<(&Camera, &GlobalTransform)>,
 
+    ghosts_q: Query<Entity, With<LineDragHoverGhost>>,
+
     mut ray_cast: MeshRayCast,
     mut drag_state: ResMut<LineDragState>,
+    mut drag_hover: ResMut<LineDragHover>,
+    mut hover_events: EventWriter<LineDragHoversStation>,
     metro: Res<Metro>,
     metro_resources: Res<MetroResources>,
+    preferences: Res<Preferences>,
+    theme: Res<Theme>,
 
     mut station_intersection_handled: Local<Option<bool>>,
 ) {
+    if trigger.button != PointerButton::Primary {
+        return;
+    }
+
     let station_intersection_handled = station_intersection_handled.get_or_insert(true);
 
     let (camera, camera_transform) = *camera_transform_q;
@@ -192,6 +241,55 @@ fn on_drag(
         None
     };
 
+    if let LineDragState::New { path, line_id } = &*drag_state {
+        let hover = intersecting_station.map(|station| {
+            let station_id = station.station_id;
+            let last_start = path.last().map(|node| node.start_station_id);
+            // Going back to the start of the current segment detaches it, so that's allowed.
+            let valid = last_start == Some(station_id)
+                || !path.iter().any(|node| node.start_station_id == station_id);
+
+            (station_id, valid)
+        });
+
+        if hover != drag_hover.0 {
+            for ghost in &ghosts_q {
+                commands.entity(ghost).despawn();
+            }
+
+            if let Some((station_id, _)) = drag_hover.0 {
+                hover_events.write(LineDragHoversStation {
+                    station_id,
+                    hovering: false,
+                    valid: true,
+                });
+            }
+
+            if let Some((station_id, valid)) = hover {
+                hover_events.write(LineDragHoversStation {
+                    station_id,
+                    hovering: true,
+                    valid,
+                });
+
+                if let Some(from) = ghost_start(path, station_id) {
+                    let color = preferences.line_color(*line_id).into();
+                    spawn_hover_ghost(
+                        &mut commands,
+                        &metro,
+                        &theme,
+                        color,
+                        from,
+                        station_id,
+                        valid,
+                    );
+                }
+            }
+
+            drag_hover.0 = hover;
+        }
+    }
+
     match &mut *drag_state {
         LineDragState::None => {}
         LineDragState::New { path, line_id } => {
@@ -207,7 +305,7 @@ fn on_drag(
                 panic!("something weird");
             };
 
-            lsat_line_2d_data.end = drag_position;
+            lsat_line_2d_data.route_to(drag_position);
 
             if let Some(intersecting_station) = intersecting_station {
                 if !*station_intersection_handled {
@@ -247,15 +345,16 @@ fn on_drag(
 
                         let station = &metro.stations[intersecting_station.station_id];
 
-                        lsat_line_2d_data.end = station.position;
+                        lsat_line_2d_data.route_to(station.position);
                         last_line_dragging.end_station_id = Some(intersecting_station.station_id);
 
                         let new_line_entity = commands
                             .spawn((
+                                GameComponent,
                                 MetroLineVisualBundle::new(
                                     station.position,
                                     drag_position,
-                                    LINE_COLORS[*line_id].into(),
+                                    preferences.line_color(*line_id).into(),
                                 ),
                                 MetroLine {
                                     start_station_id: intersecting_station.station_id,
@@ -290,10 +389,95 @@ fn on_drag(
     };
 }
 
+/// The station the segment to `station_id` starts at. If the drag went back to the start of the
+/// current segment, that's the station before it.
+fn ghost_start(path: &[PathNode], station_id: StationId) -> Option<StationId> {
+    let last = path.last()?;
+
+    if last.start_station_id != station_id {
+        return Some(last.start_station_id);
+    }
+
+    path.len().checked_sub(2).map(|i| path[i].start_station_id)
+}
+
+fn spawn_hover_ghost(
+    commands: &mut Commands,
+    metro: &Metro,
+    theme: &Theme,
+    line_color: Color,
+    from: StationId,
+    to: StationId,
+    valid: bool,
+) {
+    let color = if valid {
+        line_color.with_alpha(0.5)
+    } else {
+        Color::from(theme.error).with_alpha(0.7)
+    };
+
+    let start = metro.stations[from].position;
+    let mut ghost = MetroLineVisualBundle::new(start, start, color);
+    ghost.data.route_to(metro.stations[to].position);
+    // Above the line being drawn.
+    ghost.transform.translation.z = 0.5;
+
+    commands.spawn((GameComponent, LineDragHoverGhost, ghost));
+}
+
+fn reset_line_drag(mut drag_state: ResMut<LineDragState>, mut drag_hover: ResMut<LineDragHover>) {
+    *drag_state = LineDragState::None;
+    drag_hover.0 = None;
+}
+
+/// Aborts the line being drawn on `Action::Cancel`, a second finger touching the screen, or when
+/// the pointer leaves the window. The network is only changed when the drag ends, so throwing away
+/// the preview is enough to get back to the state from before the drag.
+///
+/// Cancelling the drag wins over everything else `Action::Cancel` does, so the systems that also
+/// react to it run before this one and leave it alone while a line is being drawn.
+fn cancel_line_drag(
+    mut commands: Commands,
+    mut cursor_left_events: EventReader<CursorLeft>,
+    lines_q: Query<Entity, With<MetroLine>>,
+    ghosts_q: Query<Entity, With<LineDragHoverGhost>>,
+    mut drag_state: ResMut<LineDragState>,
+    mut drag_hover: ResMut<LineDragHover>,
+    mut hover_events: EventWriter<LineDragHoversStation>,
+    input: ActionInput,
+    touches: Res<Touches>,
+) {
+    let cursor_left = cursor_left_events.read().count() > 0;
+    let cancelled =
+        input.just_pressed(Action::Cancel) || touches.iter().nth(1).is_some() || cursor_left;
+
+    if !cancelled || !drag_state.is_active() {
+        return;
+    }
+
+    for entity in lines_q.iter().chain(&ghosts_q) {
+        commands.entity(entity).despawn();
+    }
+
+    if let Some((station_id, _)) = drag_hover.0.take() {
+        hover_events.write(LineDragHoversStation {
+            station_id,
+            hovering: false,
+            valid: true,
+        });
+    }
+
+    // The rest of the drag is ignored by `on_drag` and `on_drag_end`.
+    *drag_state = LineDragState::None;
+}
+
 fn on_drag_end(
     trigger: Trigger<Pointer<DragEnd>>,
     mut commands: Commands,
     drag_data_q: Query<(Entity, &mut MetroLine, &mut MetroLineVisual)>,
+    ghosts_q: Query<Entity, With<LineDragHoverGhost>>,
+    mut drag_hover: ResMut<LineDragHover>,
+    mut hover_events: EventWriter<LineDragHoversStation>,
 
     camera_transform_q: Single<(&Camera, &GlobalTransform)>,
     mut drag_state: ResMut<LineDragState>,
@@ -302,7 +486,25 @@ fn on_drag_end(
 
     mut line_path_changed: EventWriter<LinePathChanged>,
 ) {
-    match &mut *drag_state {
+    if trigger.button != PointerButton::Primary {
+        return;
+    }
+
+    for ghost in &ghosts_q {
+        commands.entity(ghost).despawn();
+    }
+    if let Some((station_id, _)) = drag_hover.0.take() {
+        hover_events.write(LineDragHoversStation {
+            station_id,
+            hovering: false,
+            valid: true,
+        });
+    }
+
+    // Whatever happens below, the drag is over.
+    let mut finished_drag = std::mem::replace(&mut *drag_state, LineDragState::None);
+
+    match &mut finished_drag {
         LineDragState::None => {}
         LineDragState::New { path, line_id } => {
             let Some(last_line) = path.last() else {
@@ -323,7 +525,7 @@ fn on_drag_end(
             let mut new_path = Vec::with_capacity(path.len() + 1);
             new_path.push(path[0].start_station_id);
 
-            for line in path {
+            for line in path.iter() {
                 let Some(end_station_id) = line.end_station_id else {
                     panic!("one of the lines in the path doesn't contain `end_station_id`");
                 };
@@ -331,6 +533,11 @@ fn on_drag_end(
                 new_path.push(end_station_id);
             }
 
+            // The committed line is drawn by `lines_visual` from now on.
+            for line in path.drain(..) {
+                commands.entity(line.line_entity).despawn();
+            }
+
             println!("sending linepathchanged event");
             line_path_changed.write(LinePathChanged {
                 line_id: *line_id,
@@ -427,3 +634,55 @@ fn on_drag_end(
 //                 });
 //     }
 // }
+
+#[cfg(test)]
+mod tests {
+    use bevy::input::touch::TouchPhase;
+
+    use super::*;
+    use crate::{
+        game::events,
+        style::Theme,
+        testing::{PickedEntity, headless_app, touch},
+    };
+
+    #[test]
+    fn second_finger_cancels_the_line_drag() {
+        let metro = Metro::new();
+        let station_position = metro.stations[0].position;
+
+        let mut app = headless_app();
+        app.add_plugins((events::plugin, plugin))
+            .init_asset::<Mesh>()
+            .init_resource::<Preferences>()
+            .insert_resource(Theme::new(Preferences::default().theme))
+            .insert_resource(metro)
+            .insert_resource(MetroResources::new());
+        let station = app
+            .world_mut()
+            .spawn((
+                LineDragHandle::New { station_id: 0 },
+                Transform::from_translation(station_position.extend(0.)),
+            ))
+            .id();
+        app.world_mut().resource_mut::<PickedEntity>().0 = Some(station);
+        app.update();
+
+        touch(&mut app, 0, TouchPhase::Started, Vec2::new(300., 300.));
+        app.update();
+        touch(&mut app, 0, TouchPhase::Moved, Vec2::new(320., 300.));
+        app.update();
+        assert!(matches!(
+            *app.world().resource::<LineDragState>(),
+            LineDragState::New { .. }
+        ));
+
+        touch(&mut app, 1, TouchPhase::Started, Vec2::new(500., 300.));
+        app.update();
+        assert!(!app.world().resource::<LineDragState>().is_active());
+
+        let world = app.world_mut();
+        let lines = world.query::<&MetroLine>().iter(world).count();
+        assert_eq!(lines, 0);
+    }
+}
@@ -0,0 +1,338 @@
+use bevy::prelude::*;
+
+use crate::{
+    AppState,
+    input::{Action, ActionInput},
+    settings::Preferences,
+    style::Theme,
+};
+
+use super::{
+    GameComponent,
+    events::{LineDragHoversStation, LinePathChanged, NoticeRequested},
+    lines::LineDragState,
+    lines_visual::MetroLineVisualBundle,
+    metro::{LineId, Metro, MetroResources, StationId},
+    stations::STATION_MESH_RADIUS,
+};
+
+const FOCUS_ACTIONS: [(Action, Vec2); 4] = [
+    (Action::FocusUp, Vec2::Y),
+    (Action::FocusLeft, Vec2::NEG_X),
+    (Action::FocusDown, Vec2::NEG_Y),
+    (Action::FocusRight, Vec2::X),
+];
+/// How much worse a station off to the side of the pressed direction is than one straight ahead.
+const FOCUS_SIDEWAYS_PENALTY: f32 = 2.;
+/// The focus ring is drawn above the stations, but below the trains.
+const FOCUS_RING_Z: f32 = 2.5;
+
+pub(super) fn plugin(app: &mut App) {
+    app.init_resource::<KeyboardFocus>()
+        .init_resource::<KeyboardLine>()
+        .add_systems(
+            OnEnter(AppState::Game),
+            (reset_keyboard_line, setup_focus_ring),
+        )
+        .add_systems(
+            Update,
+            (
+                move_focus,
+                build_line,
+                (update_focus_ring, update_line_preview)
+                    .run_if(resource_changed::<KeyboardFocus>.or(resource_changed::<KeyboardLine>)),
+            )
+                .chain()
+                .run_if(in_state(AppState::Game)),
+        )
+        .add_systems(
+            Update,
+            apply_focus_ring_theme
+                .run_if(resource_changed::<Theme>)
+                .run_if(in_state(AppState::Game)),
+        );
+}
+
+/// The station the keyboard cursor is on.
+#[derive(Resource, Default)]
+pub struct KeyboardFocus(pub Option<StationId>);
+
+/// The line being built with the keyboard. Like with dragging, the network only changes once the
+/// line is committed.
+#[derive(Resource, Default)]
+pub(super) struct KeyboardLine {
+    building: Option<(LineId, Vec<StationId>)>,
+    /// The station that `LineDragHoversStation` was last sent for.
+    hovered: Option<StationId>,
+}
+
+impl KeyboardLine {
+    pub(super) fn is_building(&self) -> bool {
+        self.building.is_some()
+    }
+}
+
+#[derive(Component)]
+struct FocusRing;
+
+/// The stations added so far, and the segment to the focused station.
+#[derive(Component)]
+struct KeyboardLinePreview;
+
+fn reset_keyboard_line(mut focus: ResMut<KeyboardFocus>, mut keyboard_line: ResMut<KeyboardLine>) {
+    focus.0 = None;
+    *keyboard_line = KeyboardLine::default();
+}
+
+fn setup_focus_ring(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    theme: Res<Theme>,
+) {
+    commands.spawn((
+        GameComponent,
+        FocusRing,
+        Mesh2d(meshes.add(Annulus::new(
+            STATION_MESH_RADIUS * 1.6,
+            STATION_MESH_RADIUS * 1.9,
+        ))),
+        MeshMaterial2d(materials.add(Color::from(theme.on_background))),
+        Transform::from_translation(Vec3::Z * FOCUS_RING_Z),
+        Visibility::Hidden,
+        Pickable::IGNORE,
+    ));
+}
+
+fn apply_focus_ring_theme(
+    ring: Single<&MeshMaterial2d<ColorMaterial>, With<FocusRing>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    theme: Res<Theme>,
+) {
+    if let Some(material) = materials.get_mut(&ring.0) {
+        material.color = theme.on_background.into();
+    }
+}
+
+/// The closest station in `direction` from `from`. Stations off to the side count as further away.
+fn station_in_direction(metro: &Metro, from: StationId, direction: Vec2) -> Option<StationId> {
+    let origin = metro.stations[from].position;
+
+    metro
+        .stations
+        .iter()
+        .enumerate()
+        .filter(|(station_id, _)| *station_id != from)
+        .filter_map(|(station_id, station)| {
+            let offset = station.position - origin;
+            let ahead = offset.dot(direction);
+            let sideways = offset.perp_dot(direction).abs();
+
+            (ahead > 0.).then_some((station_id, ahead + sideways * FOCUS_SIDEWAYS_PENALTY))
+        })
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(station_id, _)| station_id)
+}
+
+/// The first arrow press focuses the station closest to the middle of the view.
+fn move_focus(
+    camera_transform: Single<&Transform, With<Camera2d>>,
+    input: ActionInput,
+    metro: Res<Metro>,
+    mut focus: ResMut<KeyboardFocus>,
+) {
+    let Some(direction) = FOCUS_ACTIONS
+        .iter()
+        .find(|(action, _)| input.just_pressed(*action))
+        .map(|(_, direction)| *direction)
+    else {
+        return;
+    };
+
+    let next = match focus.0 {
+        Some(station_id) => station_in_direction(&metro, station_id, direction),
+        None => {
+            let center = camera_transform.translation.truncate();
+            metro
+                .stations
+                .iter()
+                .enumerate()
+                .min_by(|(_, a), (_, b)| {
+                    a.position
+                        .distance(center)
+                        .total_cmp(&b.position.distance(center))
+                })
+                .map(|(station_id, _)| station_id)
+        }
+    };
+
+    if next.is_some() {
+        focus.0 = next;
+    }
+}
+
+/// Whether `station_id` can be added to the end of `path`. Loops aren't supported yet.
+fn can_add_station(path: &[StationId], station_id: StationId) -> bool {
+    !path.contains(&station_id)
+}
+
+/// Cancelling the line being built is left to the line drag while one is going on.
+pub(super) fn build_line(
+    input: ActionInput,
+    drag_state: Res<LineDragState>,
+    focus: Res<KeyboardFocus>,
+    metro: Res<Metro>,
+    metro_resources: Res<MetroResources>,
+    mut keyboard_line: ResMut<KeyboardLine>,
+    mut line_path_changed: EventWriter<LinePathChanged>,
+    mut notice_requested_events: EventWriter<NoticeRequested>,
+) {
+    if input.just_pressed(Action::Cancel)
+        && keyboard_line.building.is_some()
+        && !drag_state.is_active()
+    {
+        keyboard_line.building = None;
+        return;
+    }
+
+    if input.just_pressed(Action::RemoveStation) {
+        if let Some((_, path)) = &mut keyboard_line.building {
+            path.pop();
+            if path.is_empty() {
+                keyboard_line.building = None;
+            }
+        }
+        return;
+    }
+
+    if input.just_pressed(Action::CommitLine) {
+        let Some((line_id, path)) = keyboard_line.building.take() else {
+            return;
+        };
+
+        if path.len() < 2 {
+            notice_requested_events.write(NoticeRequested(
+                "A line needs at least two stations".to_string(),
+            ));
+            return;
+        }
+
+        line_path_changed.write(LinePathChanged {
+            line_id,
+            new_path: path,
+        });
+        return;
+    }
+
+    if !input.just_pressed(Action::AddStation) {
+        return;
+    }
+    let Some(station_id) = focus.0 else {
+        return;
+    };
+
+    match &mut keyboard_line.building {
+        Some((_, path)) => {
+            if can_add_station(path, station_id) {
+                path.push(station_id);
+            }
+        }
+        None => {
+            let active_lines = metro.get_active_lines();
+            let new_line_id = (0..metro_resources.available_lines)
+                .find(|line_id| !active_lines.contains(line_id));
+
+            let Some(new_line_id) = new_line_id else {
+                notice_requested_events.write(NoticeRequested("No lines available".to_string()));
+                return;
+            };
+
+            keyboard_line.building = Some((new_line_id, vec![station_id]));
+        }
+    }
+}
+
+fn update_focus_ring(
+    ring_q: Single<(&mut Transform, &mut Visibility), With<FocusRing>>,
+    focus: Res<KeyboardFocus>,
+    metro: Res<Metro>,
+) {
+    let (mut transform, mut visibility) = ring_q.into_inner();
+
+    match focus.0 {
+        Some(station_id) => {
+            transform.translation = metro.stations[station_id].position.extend(FOCUS_RING_Z);
+            *visibility = Visibility::Inherited;
+        }
+        None => *visibility = Visibility::Hidden,
+    }
+}
+
+/// Redraws the line being built, and highlights the focused station the same way as dragging a line
+/// over it does.
+fn update_line_preview(
+    mut commands: Commands,
+    previews_q: Query<Entity, With<KeyboardLinePreview>>,
+    mut hover_events: EventWriter<LineDragHoversStation>,
+    focus: Res<KeyboardFocus>,
+    metro: Res<Metro>,
+    preferences: Res<Preferences>,
+    theme: Res<Theme>,
+    mut keyboard_line: ResMut<KeyboardLine>,
+) {
+    for entity in &previews_q {
+        commands.entity(entity).despawn();
+    }
+
+    let hover = match (&keyboard_line.building, focus.0) {
+        (Some((_, path)), Some(station_id)) if path.last() != Some(&station_id) => Some(station_id),
+        _ => None,
+    };
+
+    if hover != keyboard_line.hovered {
+        if let Some(station_id) = keyboard_line.hovered {
+            hover_events.write(LineDragHoversStation {
+                station_id,
+                hovering: false,
+                valid: true,
+            });
+        }
+        keyboard_line.hovered = hover;
+    }
+
+    let Some((line_id, path)) = &keyboard_line.building else {
+        return;
+    };
+    let color = Color::from(preferences.line_color(*line_id));
+
+    for pair in path.windows(2) {
+        let start = metro.stations[pair[0]].position;
+        let mut segment = MetroLineVisualBundle::new(start, start, color);
+        segment.data.route_to(metro.stations[pair[1]].position);
+
+        commands.spawn((GameComponent, KeyboardLinePreview, segment));
+    }
+
+    let (Some(station_id), Some(last)) = (hover, path.last()) else {
+        return;
+    };
+    let valid = can_add_station(path, station_id);
+
+    hover_events.write(LineDragHoversStation {
+        station_id,
+        hovering: true,
+        valid,
+    });
+
+    let color = if valid {
+        color.with_alpha(0.5)
+    } else {
+        Color::from(theme.error).with_alpha(0.7)
+    };
+    let start = metro.stations[*last].position;
+    let mut ghost = MetroLineVisualBundle::new(start, start, color);
+    ghost.data.route_to(metro.stations[station_id].position);
+    ghost.transform.translation.z = 0.5;
+
+    commands.spawn((GameComponent, KeyboardLinePreview, ghost));
+}
@@ -1,59 +1,537 @@
-use bevy::prelude::*;
+use std::f32::consts::PI;
+
+use bevy::{asset::RenderAssetUsages, prelude::*, render::mesh::PrimitiveTopology};
+
+use crate::{AppState, settings::Preferences, style::Theme};
+
+use super::{
+    GameComponent,
+    events::ActiveLinesChanged,
+    geometry::{self, Bend},
+    metro::{self, LineId, LinePattern, Metro},
+};
 
 const LINE_WIDTH: f32 = 2.;
+/// How many triangles approximate a half circle in round joins and caps.
+const ROUND_SEGMENTS: usize = 8;
+/// Mitres longer than this (relative to half the line width) are replaced with round joins, so that
+/// sharp turns don't produce spikes.
+const MITER_LIMIT: f32 = 2.;
+/// How far the line goes past its last station before the terminal.
+const TERMINAL_STUB: f32 = 5.;
+/// Length of the bar across the end of the line.
+const TERMINAL_WIDTH: f32 = 6.;
 
 pub fn plugin(app: &mut App) {
-    app.add_systems(Update, update_line_position);
+    app.init_resource::<HighlightedLine>()
+        .init_resource::<SelectedLine>()
+        .add_systems(OnEnter(AppState::Game), reset_selected_line)
+        .add_systems(
+            Update,
+            (
+                sync_metro_lines
+                    .after(metro::on_line_path_changed)
+                    .run_if(on_event::<ActiveLinesChanged>),
+                highlight_lines,
+                (update_line_meshes, update_line_colors),
+            )
+                .chain(),
+        );
 }
 
-#[derive(Component)]
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum LineJoin {
+    Miter,
+    Round,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum LineCap {
+    Round,
+    /// A "T" at the end of the line, like on metro maps.
+    Terminal,
+}
+
+/// A polyline, rendered as a single triangle strip mesh. The mesh is rebuilt whenever the component
+/// changes.
+#[derive(Component, Clone, PartialEq)]
+#[require(MetroLineColor)]
 pub struct MetroLineVisual {
-    pub start: Vec2,
-    pub end: Vec2,
-    pub color: Color,
+    pub points: Vec<Vec2>,
+    pub join: LineJoin,
+    pub cap: LineCap,
+    pub pattern: LinePattern,
+}
+
+impl MetroLineVisual {
+    pub fn set_end(&mut self, end: Vec2) {
+        if let Some(last) = self.points.last_mut() {
+            *last = end;
+        }
+    }
+
+    /// Replaces everything after the first point with an octilinear route to `end`.
+    pub fn route_to(&mut self, end: Vec2) {
+        let Some(start) = self.points.first().copied() else {
+            return;
+        };
+
+        self.points =
+            geometry::smooth_corners(&geometry::octilinear_route(start, end, Bend::default()));
+    }
+
+    pub fn end(&self) -> Option<Vec2> {
+        self.points.last().copied()
+    }
+}
+
+/// The colour of a `MetroLineVisual`. Kept apart so that recolouring a line doesn't rebuild its mesh.
+#[derive(Component, Clone, Copy, PartialEq, Default)]
+pub struct MetroLineColor(pub Color);
+
+/// The visual of a line that's been committed to `Metro::lines`.
+#[derive(Component)]
+pub struct MetroLinePathVisual {
+    pub line_id: LineId,
 }
 
+/// The line under the cursor, e.g. while a train is being dragged onto it.
+#[derive(Resource, Default)]
+pub struct HighlightedLine(pub Option<LineId>);
+
+/// The line picked in the line indicator sidebar. The other lines are dimmed while one is selected.
+#[derive(Resource, Default)]
+pub struct SelectedLine(pub Option<LineId>);
+
 #[derive(Bundle)]
 pub struct MetroLineVisualBundle {
     pub data: MetroLineVisual,
+    pub color: MetroLineColor,
     pub transform: Transform,
-    pub sprite: Sprite,
 }
 
 impl MetroLineVisualBundle {
+    /// A single straight segment, e.g. the one following the cursor while the line is being drawn.
     pub fn new(start: Vec2, end: Vec2, color: Color) -> Self {
-        let direction = end - start;
-        let length = direction.length();
-        let angle = direction.y.atan2(direction.x);
-
         MetroLineVisualBundle {
-            data: MetroLineVisual { color, end, start },
-            transform: Transform {
-                translation: ((start + end) / 2.0).extend(0.0),
-                rotation: Quat::from_rotation_z(angle),
-                scale: Vec3::new(length, LINE_WIDTH, 1.0),
-                // ..Default::default()
+            data: MetroLineVisual {
+                points: vec![start, end],
+                join: LineJoin::Round,
+                cap: LineCap::Round,
+                pattern: LinePattern::Solid,
             },
-            sprite: Sprite {
-                color,
-                ..Default::default()
+            color: MetroLineColor(color),
+            transform: Transform::default(),
+        }
+    }
+
+    pub fn path(points: Vec<Vec2>, color: Color) -> Self {
+        MetroLineVisualBundle {
+            data: MetroLineVisual {
+                points,
+                join: LineJoin::Miter,
+                cap: LineCap::Terminal,
+                pattern: LinePattern::Solid,
             },
+            color: MetroLineColor(color),
+            transform: Transform::default(),
         }
     }
 }
 
-fn update_line_position(
-    lines: Query<(&MetroLineVisual, &mut Transform, &mut Sprite), Changed<MetroLineVisual>>,
+/// Keeps a `MetroLinePathVisual` for every line in `Metro::lines`.
+fn sync_metro_lines(
+    mut commands: Commands,
+    mut visuals_q: Query<(Entity, &MetroLinePathVisual, &mut MetroLineVisual)>,
+    metro: Res<Metro>,
+    preferences: Res<Preferences>,
 ) {
-    for (data, mut transform, mut sprite) in lines {
-        let direction = data.end - data.start;
-        let length = direction.length();
-        let angle = direction.y.atan2(direction.x);
+    for (entity, path_visual, mut visual) in &mut visuals_q {
+        let Some(path) = metro.lines.get(&path_visual.line_id) else {
+            commands.entity(entity).despawn();
+            continue;
+        };
+
+        let points = metro.line_geometry(path_visual.line_id);
+
+        if visual.points != points {
+            visual.points = points;
+        }
+    }
+
+    for line_id in metro.lines.keys() {
+        if visuals_q
+            .iter()
+            .any(|(_, path_visual, _)| path_visual.line_id == *line_id)
+        {
+            continue;
+        }
+
+        commands.spawn((
+            GameComponent,
+            MetroLinePathVisual { line_id: *line_id },
+            MetroLineVisualBundle::path(
+                metro.line_geometry(*line_id),
+                preferences.line_color(*line_id).into(),
+            ),
+        ));
+    }
+}
+
+fn reset_selected_line(mut selected_line: ResMut<SelectedLine>) {
+    selected_line.0 = None;
+}
+
+fn highlight_lines(
+    mut visuals_q: Query<(
+        &MetroLinePathVisual,
+        &mut MetroLineVisual,
+        &mut MetroLineColor,
+    )>,
+    highlighted_line: Res<HighlightedLine>,
+    selected_line: Res<SelectedLine>,
+    preferences: Res<Preferences>,
+    theme: Res<Theme>,
+) {
+    for (path_visual, mut visual, mut line_color) in &mut visuals_q {
+        let line_id = Some(path_visual.line_id);
+        let color = Color::from(preferences.line_color(path_visual.line_id));
+        let color = if highlighted_line.0 == line_id || selected_line.0 == line_id {
+            color.lighter(0.15)
+        } else if selected_line.0.is_some() {
+            // The materials are opaque, so the line is blended with the background by hand.
+            color.mix(&theme.background.into(), 0.7)
+        } else {
+            color
+        };
+
+        if line_color.0 != color {
+            line_color.0 = color;
+        }
+
+        let pattern = preferences.line_pattern(path_visual.line_id);
+        if visual.pattern != pattern {
+            visual.pattern = pattern;
+        }
+    }
+}
+
+fn update_line_meshes(
+    mut commands: Commands,
+    lines: Query<(Entity, &MetroLineVisual, Option<&Mesh2d>), Changed<MetroLineVisual>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+) {
+    for (entity, data, mesh) in lines {
+        let new_mesh = build_line_mesh(&data.points, LINE_WIDTH, data.join, data.cap, data.pattern);
+
+        match mesh {
+            Some(mesh) => {
+                meshes.insert(mesh.id(), new_mesh);
+            }
+            None => {
+                commands.entity(entity).insert(Mesh2d(meshes.add(new_mesh)));
+            }
+        }
+    }
+}
+
+type LineColorData<'a> = (
+    Entity,
+    &'a MetroLineColor,
+    Option<&'a MeshMaterial2d<ColorMaterial>>,
+);
+
+fn update_line_colors(
+    mut commands: Commands,
+    lines: Query<LineColorData, Changed<MetroLineColor>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    for (entity, color, material) in lines {
+        match material.and_then(|material| materials.get_mut(material.id())) {
+            Some(material) => material.color = color.0,
+            None => {
+                commands
+                    .entity(entity)
+                    .insert(MeshMaterial2d(materials.add(color.0)));
+            }
+        }
+    }
+}
+
+/// Builds a triangle strip covering the polyline. Separate parts (joins, caps, dashes) are stitched
+/// together with degenerate triangles.
+pub fn build_line_mesh(
+    points: &[Vec2],
+    width: f32,
+    join: LineJoin,
+    cap: LineCap,
+    pattern: LinePattern,
+) -> Mesh {
+    let mut points = points.to_vec();
+    points.dedup_by(|a, b| a.distance_squared(*b) < f32::EPSILON);
+
+    let half_width = width / 2.;
+    let dashes = pattern.dashes();
+
+    let strips = if dashes.is_empty() || points.len() < 2 {
+        polyline_strips(&points, half_width, join, cap)
+    } else {
+        let dashes: Vec<f32> = dashes.iter().map(|length| length * width).collect();
+        let mut strips: Vec<Vec<Vec2>> = dash_polylines(&points, &dashes)
+            .iter()
+            .flat_map(|dash| polyline_strips(dash, half_width, join, LineCap::Round))
+            .collect();
+
+        // The dashes already have round ends, only the terminals are left.
+        if cap == LineCap::Terminal {
+            let (start, end) = end_directions(&points);
+            strips.extend(cap_strips(points[0], start, half_width, cap));
+            strips.extend(cap_strips(points[points.len() - 1], end, half_width, cap));
+        }
+
+        strips
+    };
+
+    let mut positions: Vec<[f32; 3]> = Vec::new();
+    for strip in strips.iter().filter(|strip| !strip.is_empty()) {
+        if let Some(last) = positions.last().copied() {
+            positions.push(last);
+            positions.push(strip[0].extend(0.).to_array());
+        }
+
+        positions.extend(strip.iter().map(|vertex| vertex.extend(0.).to_array()));
+    }
+
+    Mesh::new(
+        PrimitiveTopology::TriangleStrip,
+        RenderAssetUsages::default(),
+    )
+    .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, positions)
+}
+
+fn polyline_strips(
+    points: &[Vec2],
+    half_width: f32,
+    join: LineJoin,
+    cap: LineCap,
+) -> Vec<Vec<Vec2>> {
+    match points {
+        [] => Vec::new(),
+        [point] => vec![polygon_strip(&arc(*point, half_width, 0., 2. * PI))],
+        _ => {
+            let mut strips = body_strips(points, half_width, join);
+
+            let (start_direction, end_direction) = end_directions(points);
+            strips.extend(cap_strips(points[0], start_direction, half_width, cap));
+            strips.extend(cap_strips(
+                points[points.len() - 1],
+                end_direction,
+                half_width,
+                cap,
+            ));
+
+            strips
+        }
+    }
+}
+
+/// The directions pointing away from both ends of the polyline.
+fn end_directions(points: &[Vec2]) -> (Vec2, Vec2) {
+    let start = (points[0] - points[1]).normalize();
+    let end = (points[points.len() - 1] - points[points.len() - 2]).normalize();
+
+    (start, end)
+}
+
+/// Cuts the polyline into the parts that are drawn. `dashes` alternates between drawn and skipped
+/// lengths, starting with a drawn one. A drawn length of 0 leaves a single point.
+fn dash_polylines(points: &[Vec2], dashes: &[f32]) -> Vec<Vec<Vec2>> {
+    let mut polylines = Vec::new();
+    let mut current = vec![points[0]];
+    let mut dash = 0;
+    let mut remaining = dashes[0];
+
+    for segment in points.windows(2) {
+        let (mut start, end) = (segment[0], segment[1]);
+
+        loop {
+            let length = start.distance(end);
+            let drawn = dash % 2 == 0;
+
+            if length <= remaining {
+                remaining -= length;
+                if drawn {
+                    current.push(end);
+                }
+                break;
+            }
+
+            // Ends the drawn part, or starts the next one.
+            let split = start.move_towards(end, remaining);
+            current.push(split);
+            if drawn {
+                polylines.push(std::mem::take(&mut current));
+            }
+
+            start = split;
+            dash = (dash + 1) % dashes.len();
+            remaining = dashes[dash];
+        }
+    }
+
+    if dash % 2 == 0 {
+        polylines.push(current);
+    }
+
+    // Dots are two copies of the same point.
+    for polyline in &mut polylines {
+        polyline.dedup_by(|a, b| a.distance_squared(*b) < f32::EPSILON);
+    }
+
+    polylines
+}
+
+/// The body of the line. Mitred joins keep it in one strip, round ones break it at every corner.
+fn body_strips(points: &[Vec2], half_width: f32, join: LineJoin) -> Vec<Vec<Vec2>> {
+    let normal = |a: Vec2, b: Vec2| (b - a).normalize().perp();
+
+    let mut strips = Vec::new();
+    let mut strip = Vec::new();
+
+    let first_normal = normal(points[0], points[1]) * half_width;
+    strip.extend([points[0] + first_normal, points[0] - first_normal]);
+
+    for i in 1..points.len() - 1 {
+        let (previous, point, next) = (points[i - 1], points[i], points[i + 1]);
+        let incoming = normal(previous, point);
+        let outgoing = normal(point, next);
+
+        let miter = (incoming + outgoing).normalize_or_zero();
+        let miter_scale = 1. / miter.dot(outgoing).max(f32::EPSILON);
+
+        if join == LineJoin::Miter && miter != Vec2::ZERO && miter_scale <= MITER_LIMIT {
+            let offset = miter * half_width * miter_scale;
+            strip.extend([point + offset, point - offset]);
+        } else {
+            strip.extend([point + incoming * half_width, point - incoming * half_width]);
+            strips.push(std::mem::take(&mut strip));
+            strips.push(polygon_strip(&arc(point, half_width, 0., 2. * PI)));
+            strip.extend([point + outgoing * half_width, point - outgoing * half_width]);
+        }
+    }
+
+    let last = points[points.len() - 1];
+    let last_normal = normal(points[points.len() - 2], last) * half_width;
+    strip.extend([last + last_normal, last - last_normal]);
+    strips.push(strip);
+
+    strips
+}
+
+/// `direction` points away from the line.
+fn cap_strips(end: Vec2, direction: Vec2, half_width: f32, cap: LineCap) -> Vec<Vec<Vec2>> {
+    let normal = direction.perp();
+
+    match cap {
+        LineCap::Round => {
+            let start_angle = normal.to_angle();
+            vec![polygon_strip(&arc(end, half_width, start_angle, -PI))]
+        }
+        LineCap::Terminal => {
+            let stub_end = end + direction * TERMINAL_STUB;
+            let stub = vec![
+                end + normal * half_width,
+                end - normal * half_width,
+                stub_end + normal * half_width,
+                stub_end - normal * half_width,
+            ];
+
+            let bar_half_length = normal * TERMINAL_WIDTH / 2.;
+            let bar_half_width = direction * half_width;
+            let bar = vec![
+                stub_end - bar_half_width + bar_half_length,
+                stub_end - bar_half_width - bar_half_length,
+                stub_end + bar_half_width + bar_half_length,
+                stub_end + bar_half_width - bar_half_length,
+            ];
+
+            vec![stub, bar]
+        }
+    }
+}
+
+/// Points on a circular arc, starting at `start_angle` and sweeping by `sweep` radians.
+fn arc(center: Vec2, radius: f32, start_angle: f32, sweep: f32) -> Vec<Vec2> {
+    let segments = ((sweep.abs() / PI) * ROUND_SEGMENTS as f32).ceil().max(1.) as usize;
+    let is_full_circle = sweep.abs() >= 2. * PI;
+    let count = if is_full_circle {
+        segments
+    } else {
+        segments + 1
+    };
+
+    (0..count)
+        .map(|i| {
+            let angle = start_angle + sweep * i as f32 / segments as f32;
+            center + Vec2::from_angle(angle) * radius
+        })
+        .collect()
+}
+
+/// Orders the vertices of a convex polygon so that they form a triangle strip covering it.
+fn polygon_strip(vertices: &[Vec2]) -> Vec<Vec2> {
+    let mut strip = Vec::with_capacity(vertices.len());
+    let (mut front, mut back) = (0, vertices.len());
+
+    while front < back {
+        strip.push(vertices[front]);
+        front += 1;
+
+        if front < back {
+            back -= 1;
+            strip.push(vertices[back]);
+        }
+    }
+
+    strip
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::headless_app;
+
+    #[test]
+    fn recoloring_a_line_keeps_its_mesh() {
+        let mut app = headless_app();
+        app.init_asset::<Mesh>()
+            .init_asset::<ColorMaterial>()
+            .add_systems(Update, (update_line_meshes, update_line_colors));
+        let line = app
+            .world_mut()
+            .spawn(MetroLineVisualBundle::path(
+                vec![Vec2::ZERO, Vec2::X * 10.],
+                Color::BLACK,
+            ))
+            .id();
+        app.update();
+        app.world_mut()
+            .resource_mut::<Events<AssetEvent<Mesh>>>()
+            .clear();
 
-        transform.translation = ((data.start + data.end) / 2.0).extend(0.0);
-        transform.rotation = Quat::from_rotation_z(angle);
-        transform.scale = Vec3::new(length, LINE_WIDTH, 1.0);
+        app.world_mut().get_mut::<MetroLineColor>(line).unwrap().0 = Color::WHITE;
+        app.update();
 
-        sprite.color = data.color;
+        let material = app
+            .world()
+            .get::<MeshMaterial2d<ColorMaterial>>(line)
+            .unwrap();
+        let materials = app.world().resource::<Assets<ColorMaterial>>();
+        assert_eq!(materials.get(material.id()).unwrap().color, Color::WHITE);
+        assert!(
+            app.world()
+                .resource::<Events<AssetEvent<Mesh>>>()
+                .is_empty()
+        );
     }
 }
@@ -6,15 +6,41 @@ use bevy::prelude::*;
 use bevy::{
     color::Srgba,
     math::Vec2,
-    platform::collections::HashSet,
+    platform::collections::{HashMap, HashSet},
     prelude::{Deref, Resource},
 };
 
-use crate::AppState;
+use crate::{AppState, GameState};
 
-use super::events::{ActiveLinesChanged, LinePathChanged};
+use super::clock::{DemandProfiles, District, GameClock};
+use super::events::{ActiveLinesChanged, LinePathChanged, PassengerAbandoned, PassengerDelivered};
+use super::geometry::{self, ArcLength, Bend};
+use super::history::{History, NetworkEdit};
 
 pub const MAP_SIZE: Vec2 = Vec2::new(200., 200.);
+/// How often (in real seconds) every station gets a chance to spawn a passenger.
+const PASSENGER_SPAWN_INTERVAL: f32 = 1.0;
+/// World units per real second.
+pub const TRAIN_SPEED: f32 = 20.;
+/// World units per real second squared. Used for both speeding up and braking.
+pub const TRAIN_ACCELERATION: f32 = 15.;
+/// Trains crawl the last bit into the station instead of stopping just short of it.
+const TRAIN_MIN_SPEED: f32 = 1.;
+/// How long (in real seconds) a train stands at a station even if nobody gets on or off.
+pub const BASE_DWELL_TIME: f32 = 0.3;
+/// Added to the dwell time for every passenger getting on or off.
+pub const DWELL_TIME_PER_PASSENGER: f32 = 0.15;
+/// How many passengers fit into a single locomotive.
+pub const TRAIN_CAPACITY: usize = 6;
+/// How long (in in-game minutes) a passenger is willing to wait at stations before giving up. Time
+/// spent on trains doesn't count, but the waits before every transfer add up.
+pub const PASSENGER_PATIENCE: f32 = 120.;
+/// How many passengers can wait at a station before it becomes overcrowded.
+pub const STATION_CAPACITY: usize = 12;
+/// How long (in in-game minutes) a station can stay overcrowded before the run ends.
+pub const OVERCROWDING_LIMIT: f32 = 180.;
+/// Distance between the centers of lines running side by side.
+pub const LANE_SPACING: f32 = 2.5;
 pub const LINE_COLORS: [Srgba; 10] = [
     Srgba::new(0.4, 0.8, 0.9, 1.0), // soft cyan
     Srgba::new(0.9, 0.6, 0.4, 1.0), // warm peach
@@ -27,32 +53,244 @@ pub const LINE_COLORS: [Srgba; 10] = [
     Srgba::new(0.9, 0.8, 0.4, 1.0), // mellow yellow
     Srgba::new(0.6, 0.4, 0.9, 1.0), // soft violet
 ];
+/// Based on the Okabe-Ito palette, so that red-green colour blind players can tell the first eight
+/// apart. The rest repeat its hues at a different lightness, and rely on `LINE_PATTERNS`.
+pub const COLOR_BLIND_LINE_COLORS: [Srgba; 10] = [
+    Srgba::new(0.0, 0.45, 0.7, 1.0),   // blue
+    Srgba::new(0.9, 0.62, 0.0, 1.0),   // orange
+    Srgba::new(0.34, 0.71, 0.91, 1.0), // sky blue
+    Srgba::new(0.0, 0.62, 0.45, 1.0),  // bluish green
+    Srgba::new(0.94, 0.89, 0.26, 1.0), // yellow
+    Srgba::new(0.84, 0.37, 0.0, 1.0),  // vermillion
+    Srgba::new(0.8, 0.47, 0.65, 1.0),  // reddish purple
+    Srgba::new(0.6, 0.6, 0.6, 1.0),    // gray
+    Srgba::new(0.0, 0.3, 0.47, 1.0),   // dark blue
+    Srgba::new(1.0, 0.82, 0.55, 1.0),  // light orange
+];
+/// Fully saturated, for the black background of the high contrast palette.
+pub const HIGH_CONTRAST_LINE_COLORS: [Srgba; 10] = [
+    Srgba::new(0.0, 0.9, 1.0, 1.0), // cyan
+    Srgba::new(1.0, 0.5, 0.0, 1.0), // orange
+    Srgba::new(1.0, 1.0, 0.0, 1.0), // yellow
+    Srgba::new(0.0, 1.0, 0.3, 1.0), // green
+    Srgba::new(1.0, 0.2, 0.8, 1.0), // magenta
+    Srgba::new(1.0, 1.0, 1.0, 1.0), // white
+    Srgba::new(1.0, 0.2, 0.2, 1.0), // red
+    Srgba::new(0.3, 0.5, 1.0, 1.0), // blue
+    Srgba::new(0.7, 1.0, 0.5, 1.0), // lime
+    Srgba::new(0.7, 0.4, 1.0, 1.0), // violet
+];
+/// Drawn when `Preferences::line_patterns` is on, so that the lines can be told apart without
+/// their colours.
+pub const LINE_PATTERNS: [LinePattern; 10] = [
+    LinePattern::Solid,
+    LinePattern::Dashed,
+    LinePattern::Dotted,
+    LinePattern::DashDot,
+    LinePattern::LongDash,
+    LinePattern::DashDotDot,
+    LinePattern::Solid,
+    LinePattern::Dashed,
+    LinePattern::Dotted,
+    LinePattern::DashDot,
+];
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LinePattern {
+    Solid,
+    Dashed,
+    Dotted,
+    DashDot,
+    LongDash,
+    DashDotDot,
+}
+
+impl LinePattern {
+    /// Alternating lengths of the drawn and skipped parts, in line widths. The drawn parts get round
+    /// ends on top of their length, so a length of 0 is a dot. Empty for a solid line.
+    pub fn dashes(self) -> &'static [f32] {
+        match self {
+            LinePattern::Solid => &[],
+            LinePattern::Dashed => &[2., 2.5],
+            LinePattern::Dotted => &[0., 2.],
+            LinePattern::DashDot => &[2.5, 2., 0., 2.],
+            LinePattern::LongDash => &[5., 2.5],
+            LinePattern::DashDotDot => &[2.5, 2., 0., 2., 0., 2.],
+        }
+    }
+}
 
 pub(super) fn plugin(app: &mut App) {
     app.insert_resource(Metro::new())
         .insert_resource(MetroResources::new())
+        .insert_resource(PassengerSpawnTimer(Timer::from_seconds(
+            PASSENGER_SPAWN_INTERVAL,
+            TimerMode::Repeating,
+        )))
         .add_systems(
             Update,
-            on_line_path_changed.run_if(in_state(AppState::Game)),
-        );
+            (on_line_path_changed, deliver_from_removed_trains).run_if(in_state(AppState::Game)),
+        )
+        .add_systems(
+            Update,
+            (
+                spawn_passengers,
+                run_trains,
+                abandon_stations,
+                check_overcrowding,
+            )
+                .chain()
+                .run_if(in_state(GameState::Running)),
+        )
+        .add_systems(OnExit(AppState::Game), reset_metro);
+}
+
+pub fn reset_metro(mut metro: ResMut<Metro>, mut metro_resources: ResMut<MetroResources>) {
+    *metro = Metro::new();
+    *metro_resources = MetroResources::new();
+}
+
+/// Why the last run ended. Read by the results screen, after the metro itself has been reset.
+#[derive(Resource, Clone, Copy)]
+pub struct GameOver {
+    /// The station that stayed overcrowded for too long.
+    pub station_id: StationId,
+    pub kind: StationKind,
+    pub district: District,
+}
+
+#[derive(Resource, Deref, DerefMut)]
+struct PassengerSpawnTimer(Timer);
+
+fn spawn_passengers(
+    mut timer: ResMut<PassengerSpawnTimer>,
+    mut metro: ResMut<Metro>,
+    time: Res<Time>,
+    clock: Res<GameClock>,
+    demand_profiles: Res<DemandProfiles>,
+) {
+    if !timer.tick(time.delta()).just_finished() {
+        return;
+    }
+
+    metro.spawn_random_passengers(clock.minutes, |station| {
+        demand_profiles.multiplier(station.district, &clock)
+    });
+}
+
+fn run_trains(
+    mut metro: ResMut<Metro>,
+    mut delivered_events: EventWriter<PassengerDelivered>,
+    time: Res<Time>,
+    clock: Res<GameClock>,
+) {
+    metro.move_trains(time.delta_secs());
+
+    for (station_id, passenger) in metro.serve_stopped_trains(clock.minutes, time.delta_secs()) {
+        delivered_events.write(PassengerDelivered {
+            passenger,
+            station_id,
+            time: clock.minutes,
+        });
+    }
+}
+
+/// Trains can be removed while the game is paused, so this isn't left to `run_trains`.
+fn deliver_from_removed_trains(
+    mut metro: ResMut<Metro>,
+    mut delivered_events: EventWriter<PassengerDelivered>,
+) {
+    for (station_id, passenger, time) in metro.removed_train_deliveries.drain(..) {
+        delivered_events.write(PassengerDelivered {
+            passenger,
+            station_id,
+            time,
+        });
+    }
+}
+
+fn abandon_stations(
+    mut metro: ResMut<Metro>,
+    mut abandoned_events: EventWriter<PassengerAbandoned>,
+    clock: Res<GameClock>,
+) {
+    for (station_id, passenger) in metro.remove_impatient_passengers(clock.minutes) {
+        abandoned_events.write(PassengerAbandoned {
+            passenger,
+            station_id,
+            time: clock.minutes,
+        });
+    }
+}
+
+fn check_overcrowding(
+    mut commands: Commands,
+    mut metro: ResMut<Metro>,
+    mut previous_minutes: Local<Option<f32>>,
+    clock: Res<GameClock>,
+) {
+    let delta_minutes = clock.minutes - previous_minutes.unwrap_or(clock.minutes);
+    *previous_minutes = Some(clock.minutes);
+
+    if let Some(station_id) = metro.update_overcrowding(delta_minutes.max(0.)) {
+        info!("station {station_id} has been overcrowded for too long, ending the run");
+        let station = &metro.stations[station_id];
+        commands.insert_resource(GameOver {
+            station_id,
+            kind: station.kind,
+            district: station.district,
+        });
+        *previous_minutes = None;
+        commands.set_state(AppState::Results);
+    }
 }
 
 pub fn on_line_path_changed(
     mut line_path_changed_events: EventReader<LinePathChanged>,
     mut active_lines_changed_events: EventWriter<ActiveLinesChanged>,
     mut metro: ResMut<Metro>,
+    mut history: ResMut<History>,
+    metro_resources: Res<MetroResources>,
 ) {
     for event in line_path_changed_events.read() {
         if event.new_path.is_empty() {
             continue;
         }
 
-        let previous_station_id = event.new_path[0];
+        let before = metro.lines.get(&event.line_id).cloned().unwrap_or_default();
+        metro.set_line_path(event.line_id, event.new_path.clone());
 
-        for station_id in &event.new_path {
-            metro.add_connection(previous_station_id, *station_id, event.line_id);
+        let mut edits = vec![NetworkEdit::SetLinePath {
+            line_id: event.line_id,
+            before,
+            after: event.new_path.clone(),
+        }];
+
+        // New lines get their first train from the inventory, if there's one left.
+        if event.new_path.len() > 1
+            && !metro
+                .trains
+                .iter()
+                .any(|train| train.line_id == event.line_id)
+            && metro_resources.available_trains(&metro) > 0
+        {
+            let train_id = metro.new_train_id();
+            let point = LinePoint {
+                line_id: event.line_id,
+                from: event.new_path[0],
+                to: event.new_path[1],
+                distance_along: 0.,
+            };
+            metro.place_train(point, train_id);
+            edits.push(NetworkEdit::PlaceTrain {
+                train_id,
+                point,
+                carriages: 0,
+            });
         }
 
+        history.record(edits);
+
         active_lines_changed_events.write(ActiveLinesChanged);
     }
 }
@@ -67,7 +305,88 @@ pub type StationKind = usize;
 
 #[derive(Clone, Copy, Deref)]
 pub struct Passenger {
+    #[deref]
     pub target: StationKind,
+    pub origin: StationId,
+
+    // All the times are in-game minutes, as in `GameClock::minutes`.
+    pub spawned_at: f32,
+    /// When the passenger arrived at the station they're currently waiting at.
+    pub waiting_since: f32,
+    /// `None` while the passenger isn't on a train.
+    pub boarded_at: Option<f32>,
+    /// Total time spent on trains, not including the current ride.
+    pub ride_time: f32,
+
+    pub transfers: u32,
+    pub current_line: Option<LineId>,
+    /// Bit set of the lines the passenger has ridden. Kept as bits so that `Passenger` stays `Copy`,
+    /// which leaves out lines past `u32::BITS`. There are never that many.
+    lines_used: u32,
+}
+
+impl Passenger {
+    pub fn new(target: StationKind, origin: StationId, now: f32) -> Self {
+        Passenger {
+            target,
+            origin,
+            spawned_at: now,
+            waiting_since: now,
+            boarded_at: None,
+            ride_time: 0.,
+            transfers: 0,
+            current_line: None,
+            lines_used: 0,
+        }
+    }
+
+    pub fn lines_used(&self) -> impl Iterator<Item = LineId> {
+        let lines_used = self.lines_used;
+        (0..u32::BITS as LineId).filter(move |line_id| lines_used & (1 << line_id) != 0)
+    }
+
+    /// How long the passenger has been waiting at their current station.
+    pub fn current_wait(&self, now: f32) -> f32 {
+        now - self.waiting_since
+    }
+
+    /// Whether the passenger has run out of patience. Only checked while they're at a station, a
+    /// passenger never leaves a moving train.
+    pub fn is_out_of_patience(&self, now: f32) -> bool {
+        self.total_wait(now) > PASSENGER_PATIENCE
+    }
+
+    /// Time spent at stations during the whole journey so far.
+    pub fn total_wait(&self, now: f32) -> f32 {
+        let current_ride = self.boarded_at.map_or(0., |boarded_at| now - boarded_at);
+        now - self.spawned_at - self.ride_time - current_ride
+    }
+
+    fn board(&mut self, line_id: LineId, now: f32) {
+        if self
+            .current_line
+            .is_some_and(|current_line| current_line != line_id)
+        {
+            self.transfers += 1;
+        }
+
+        self.current_line = Some(line_id);
+        if let Some(bit) = u32::try_from(line_id)
+            .ok()
+            .and_then(|line_id| 1u32.checked_shl(line_id))
+        {
+            self.lines_used |= bit;
+        }
+        self.boarded_at = Some(now);
+    }
+
+    fn alight(&mut self, now: f32) {
+        if let Some(boarded_at) = self.boarded_at.take() {
+            self.ride_time += now - boarded_at;
+        }
+
+        self.waiting_since = now;
+    }
 }
 
 #[derive(Clone)]
@@ -77,6 +396,11 @@ pub struct Station {
 
     /// How often new people spawn at the station. From 0.0 to 1.0.
     pub intensity: f32,
+    /// Decides how `intensity` changes over the course of the day.
+    pub district: District,
+    /// For how long (in in-game minutes) the station has had more than `STATION_CAPACITY`
+    /// passengers waiting.
+    pub overcrowded_for: f32,
 
     pub position: Vec2,
 }
@@ -87,34 +411,74 @@ impl Station {
             kind,
             passengers: Vec::new(),
             intensity: 0.5,
+            district: District::Mixed,
+            overcrowded_for: 0.,
             position,
         }
     }
+
+    pub fn with_district(mut self, district: District) -> Self {
+        self.district = district;
+        self
+    }
 }
 pub type StationId = usize;
 
 pub type LineId = usize;
 
+/// Stays the same for the whole life of the train, unlike its index in `Metro::trains`.
+pub type TrainId = usize;
+
 pub struct Train {
+    pub id: TrainId,
+    pub line_id: LineId,
     pub passengers: Vec<Passenger>,
     pub locomotive_count: usize,
+    /// Carriages added from the inventory. Each one holds as many passengers as a locomotive.
+    pub carriage_count: usize,
     pub last_station: StationId,
+    /// If the train is `stopped`, this is the station it's standing at.
     pub next_station: StationId,
     pub traveled_distance: f32,
+    /// World units per real second.
+    pub speed: f32,
     pub stopped: bool,
+    /// How much longer (in real seconds) the train stays at the station. `None` if it has just
+    /// arrived and the passengers haven't been exchanged yet.
+    pub dwell: Option<f32>,
 }
 
 impl Train {
-    pub fn new(last_station: StationId, next_station: StationId) -> Self {
+    /// Creates a train standing at `station_id`.
+    pub fn new(id: TrainId, line_id: LineId, station_id: StationId) -> Self {
         Train {
+            id,
+            line_id,
             passengers: Vec::new(),
             locomotive_count: 1,
-            last_station,
-            next_station,
+            carriage_count: 0,
+            last_station: station_id,
+            next_station: station_id,
             traveled_distance: 0.0,
+            speed: 0.0,
             stopped: true,
+            dwell: None,
         }
     }
+
+    pub fn capacity(&self) -> usize {
+        (self.locomotive_count + self.carriage_count) * TRAIN_CAPACITY
+    }
+}
+
+/// A point on one of the segments of a line.
+#[derive(Clone, Copy)]
+pub struct LinePoint {
+    pub line_id: LineId,
+    pub from: StationId,
+    pub to: StationId,
+    /// Distance from `from` along the segment.
+    pub distance_along: f32,
 }
 
 #[derive(Clone, Copy, PartialEq, Eq)]
@@ -130,7 +494,16 @@ pub struct Metro {
     /// [Starting station id] = The ids of all the stations it has a direct connection to + id of the line
     /// which connects to it.
     pub connections: Vec<Vec<Vec<Connection>>>,
+    /// The stations each line goes through, in order.
+    pub lines: HashMap<LineId, Vec<StationId>>,
+    /// The octilinear polyline between two neighbouring stations of a line, in the direction of the
+    /// line's path. Corners aren't smoothed yet, see `segment_path`.
+    pub routes: HashMap<(LineId, StationId, StationId), Vec<Vec2>>,
     pub trains: Vec<Train>,
+    next_train_id: TrainId,
+    /// Passengers who got off at their target when their train was taken off the line, with the
+    /// time they got there. Sent out by `deliver_from_removed_trains`.
+    removed_train_deliveries: Vec<(StationId, Passenger, f32)>,
 
     pub distances: Vec<Vec<f32>>,
 }
@@ -139,12 +512,16 @@ impl Metro {
     pub fn new() -> Self {
         Metro {
             stations: vec![
-                Station::new(0, Vec2::new(-30., -20.)),
-                Station::new(1, Vec2::new(20., -20.)),
+                Station::new(0, Vec2::new(-30., -20.)).with_district(District::Residential),
+                Station::new(1, Vec2::new(20., -20.)).with_district(District::Commercial),
                 Station::new(2, Vec2::new(-20., 40.)),
             ],
             connections: vec![vec![Vec::new(); 3]; 3],
+            lines: HashMap::new(),
+            routes: HashMap::new(),
             trains: Vec::new(),
+            next_train_id: 0,
+            removed_train_deliveries: Vec::new(),
             distances: Vec::new(),
         }
     }
@@ -192,6 +569,310 @@ impl Metro {
         // true
     }
 
+    /// Replaces the path of the line. Trains that are off the new path are moved to its start.
+    pub fn set_line_path(&mut self, line_id: LineId, path: Vec<StationId>) {
+        for connection in self.connections.iter_mut().flatten() {
+            connection.retain(|connection| connection.line_id != line_id);
+        }
+
+        for pair in path.windows(2) {
+            self.add_connection(pair[0], pair[1], line_id);
+            self.add_connection(pair[1], pair[0], line_id);
+        }
+        self.calculate_distances();
+
+        self.routes
+            .retain(|(route_line_id, _, _), _| *route_line_id != line_id);
+        for pair in path.windows(2) {
+            let route = self.least_overlapping_route(pair[0], pair[1]);
+            self.routes.insert((line_id, pair[0], pair[1]), route);
+        }
+
+        for train in &mut self.trains {
+            if train.line_id == line_id
+                && !(path.contains(&train.last_station) && path.contains(&train.next_station))
+            {
+                *train = Train {
+                    passengers: std::mem::take(&mut train.passengers),
+                    locomotive_count: train.locomotive_count,
+                    carriage_count: train.carriage_count,
+                    ..Train::new(train.id, line_id, path[0])
+                };
+            }
+        }
+
+        self.lines.insert(line_id, path);
+    }
+
+    /// Takes the line off the map, along with its trains.
+    pub fn remove_line(&mut self, line_id: LineId, now: f32) {
+        for connection in self.connections.iter_mut().flatten() {
+            connection.retain(|connection| connection.line_id != line_id);
+        }
+        self.calculate_distances();
+
+        self.routes
+            .retain(|(route_line_id, _, _), _| *route_line_id != line_id);
+        self.lines.remove(&line_id);
+
+        while let Some(train_index) = self
+            .trains
+            .iter()
+            .position(|train| train.line_id == line_id)
+        {
+            self.remove_train(train_index, now);
+        }
+    }
+
+    /// Whether `from` and `to` are neighbours on the line, or the same station of it.
+    pub fn has_segment(&self, line_id: LineId, from: StationId, to: StationId) -> bool {
+        let Some(path) = self.lines.get(&line_id) else {
+            return false;
+        };
+
+        if from == to {
+            return path.contains(&from);
+        }
+
+        path.windows(2)
+            .any(|pair| (pair[0], pair[1]) == (from, to) || (pair[1], pair[0]) == (from, to))
+    }
+
+    /// Picks the bend of the octilinear connection that runs on top of the other lines the least.
+    /// Lines connecting the same pair of stations share the route, and are drawn side by side.
+    fn least_overlapping_route(&self, a: StationId, b: StationId) -> Vec<Vec2> {
+        for ((_, from, to), route) in &self.routes {
+            if (*from, *to) == (a, b) {
+                return route.clone();
+            }
+            if (*from, *to) == (b, a) {
+                return route.iter().rev().copied().collect();
+            }
+        }
+
+        let (a_position, b_position) = (self.stations[a].position, self.stations[b].position);
+
+        let overlap = |route: &Vec<Vec2>| {
+            self.routes
+                .values()
+                .map(|other_route| geometry::overlap_length(route, other_route))
+                .sum::<f32>()
+        };
+
+        let straight_first =
+            geometry::octilinear_route(a_position, b_position, Bend::StraightFirst);
+        let diagonal_first =
+            geometry::octilinear_route(a_position, b_position, Bend::DiagonalFirst);
+
+        if overlap(&diagonal_first) < overlap(&straight_first) {
+            diagonal_first
+        } else {
+            straight_first
+        }
+    }
+
+    /// How far to the side the line runs between the two stations, so that it doesn't cover the
+    /// other lines connecting them. The offset is to the left of `canonical_direction`.
+    pub fn lane_offset(&self, line_id: LineId, a: StationId, b: StationId) -> f32 {
+        let mut line_ids = self
+            .routes
+            .keys()
+            .filter(|(_, from, to)| (*from, *to) == (a, b) || (*from, *to) == (b, a))
+            .map(|(route_line_id, _, _)| *route_line_id)
+            .collect::<Vec<_>>();
+        // Sorting by id keeps the lines in the same order on every segment they share.
+        line_ids.sort();
+        line_ids.dedup();
+
+        let Some(index) = line_ids.iter().position(|id| *id == line_id) else {
+            return 0.;
+        };
+
+        (index as f32 - (line_ids.len() - 1) as f32 / 2.) * LANE_SPACING
+    }
+
+    /// The geometry trains follow and lines are drawn along between two neighbouring stations.
+    pub fn segment_path(&self, line_id: LineId, from: StationId, to: StationId) -> Vec<Vec2> {
+        let route = if let Some(route) = self.routes.get(&(line_id, from, to)) {
+            route.clone()
+        } else if let Some(route) = self.routes.get(&(line_id, to, from)) {
+            route.iter().rev().copied().collect()
+        } else {
+            vec![self.stations[from].position, self.stations[to].position]
+        };
+
+        let (from_position, to_position) =
+            (self.stations[from].position, self.stations[to].position);
+        let direction = to_position - from_position;
+        let offset = if geometry::canonical_direction(direction) == direction {
+            self.lane_offset(line_id, from, to)
+        } else {
+            -self.lane_offset(line_id, from, to)
+        };
+
+        geometry::smooth_corners(&geometry::offset_polyline(&route, offset))
+    }
+
+    /// The geometry of the whole line, from its first station to the last one.
+    pub fn line_geometry(&self, line_id: LineId) -> Vec<Vec2> {
+        let Some(path) = self.lines.get(&line_id) else {
+            return Vec::new();
+        };
+
+        let mut points = Vec::new();
+
+        // With lane offsets, neighbouring segments don't necessarily meet at the station.
+        for pair in path.windows(2) {
+            points.extend(self.segment_path(line_id, pair[0], pair[1]));
+        }
+        points.dedup_by(|a, b| a.distance_squared(*b) < f32::EPSILON);
+
+        points
+    }
+
+    /// The point on any line that's closest to `position`, if it's within `max_distance`.
+    pub fn closest_line_point(&self, position: Vec2, max_distance: f32) -> Option<LinePoint> {
+        let mut closest: Option<(LinePoint, f32)> = None;
+
+        for (line_id, path) in &self.lines {
+            for pair in path.windows(2) {
+                let segment = ArcLength::new(self.segment_path(*line_id, pair[0], pair[1]));
+                let (distance_along, distance) = segment.project(position);
+
+                if distance > max_distance
+                    || closest.is_some_and(|(_, closest_distance)| closest_distance <= distance)
+                {
+                    continue;
+                }
+
+                let point = LinePoint {
+                    line_id: *line_id,
+                    from: pair[0],
+                    to: pair[1],
+                    distance_along,
+                };
+                closest = Some((point, distance));
+            }
+        }
+
+        closest.map(|(point, _)| point)
+    }
+
+    pub fn new_train_id(&mut self) -> TrainId {
+        self.next_train_id += 1;
+        self.next_train_id - 1
+    }
+
+    pub fn train_index(&self, train_id: TrainId) -> Option<usize> {
+        self.trains.iter().position(|train| train.id == train_id)
+    }
+
+    pub fn train(&self, train_id: TrainId) -> Option<&Train> {
+        self.trains.iter().find(|train| train.id == train_id)
+    }
+
+    /// Puts a new train on the line at `point`, heading towards `point.to`. Trains placed at the
+    /// very start of a segment wait at its first station.
+    pub fn place_train(&mut self, point: LinePoint, train_id: TrainId) {
+        if point.distance_along <= 0. {
+            self.trains
+                .push(Train::new(train_id, point.line_id, point.from));
+            return;
+        }
+
+        self.trains.push(Train {
+            next_station: point.to,
+            traveled_distance: point.distance_along,
+            stopped: false,
+            ..Train::new(train_id, point.line_id, point.from)
+        });
+    }
+
+    /// Where the train is on its line.
+    pub fn train_point(&self, train: &Train) -> LinePoint {
+        LinePoint {
+            line_id: train.line_id,
+            from: train.last_station,
+            to: train.next_station,
+            distance_along: train.traveled_distance,
+        }
+    }
+
+    /// Takes the train off its line. Passengers on board get off at the station the train is
+    /// standing at, or else at the last one it stopped at. Those who were going there are delivered.
+    pub fn remove_train(&mut self, train_index: usize, now: f32) -> Train {
+        let mut train = self.trains.remove(train_index);
+        let station_id = if train.stopped {
+            train.next_station
+        } else {
+            train.last_station
+        };
+        let station_kind = self.stations[station_id].kind;
+
+        for mut passenger in train.passengers.drain(..) {
+            passenger.alight(now);
+
+            if passenger.target == station_kind {
+                self.removed_train_deliveries
+                    .push((station_id, passenger, now));
+            } else {
+                self.stations[station_id].passengers.push(passenger);
+            }
+        }
+
+        train
+    }
+
+    /// Where the train is and which way it's facing.
+    pub fn train_position(&self, train: &Train) -> (Vec2, Vec2) {
+        self.train_path(train).sample(train.traveled_distance)
+    }
+
+    fn train_path(&self, train: &Train) -> ArcLength {
+        ArcLength::new(self.segment_path(train.line_id, train.last_station, train.next_station))
+    }
+
+    /// The station a train should go to after stopping at `current`, having come from `last`.
+    /// Trains turn around at the ends of the line.
+    pub fn next_stop(
+        &self,
+        line_id: LineId,
+        last: StationId,
+        current: StationId,
+    ) -> Option<StationId> {
+        let path = self.lines.get(&line_id)?;
+        let index = path.iter().position(|station_id| *station_id == current)?;
+
+        let came_from_behind = index > 0 && path[index - 1] == last;
+        let forward = path.get(index + 1).copied();
+        let backward = index.checked_sub(1).map(|i| path[i]);
+
+        if came_from_behind || last == current {
+            forward.or(backward)
+        } else {
+            backward.or(forward)
+        }
+    }
+
+    /// Shortest distance through the network from the station to any station of the given kind.
+    pub fn distance_to_kind(&self, station_id: StationId, kind: StationKind) -> f32 {
+        if self.stations[station_id].kind == kind {
+            return 0.;
+        }
+
+        self.stations
+            .iter()
+            .enumerate()
+            .filter(|(_, station)| station.kind == kind)
+            .filter_map(|(target_id, _)| {
+                self.distances
+                    .get(station_id)
+                    .and_then(|distances| distances.get(target_id))
+            })
+            .copied()
+            .fold(f32::INFINITY, f32::min)
+    }
+
     pub fn spawn_random_station(&mut self) {
         let mut rng = rng();
 
@@ -206,27 +887,178 @@ impl Metro {
             _ => 2,
         };
 
+        let district = match rng.random_range(0..3) {
+            0 => District::Residential,
+            1 => District::Commercial,
+            _ => District::Mixed,
+        };
+
         let intensity = rng.random_range(0.1..=0.2);
-        let mut station = Station::new(kind, position);
+        let mut station = Station::new(kind, position).with_district(district);
         station.intensity = intensity;
 
         self.stations.push(station);
     }
 
-    pub fn spawn_random_passengers(&mut self) {
+    /// `demand` returns the multiplier applied to the intensity of the given station, e.g. to
+    /// simulate rush hours.
+    pub fn spawn_random_passengers(&mut self, now: f32, demand: impl Fn(&Station) -> f32) {
         let mut rng = rng();
 
+        for (station_id, station) in self.stations.iter_mut().enumerate() {
+            let intensity = (station.intensity * demand(station)).clamp(0.0, 1.0);
+
+            if rng.random::<f32>() < intensity {
+                let target = match rng.random_range(0..3) {
+                    0 => 2,
+                    1 => 1,
+                    _ => 0,
+                };
+
+                // Nobody takes the metro to a station just like the one they're at.
+                if target == station.kind {
+                    continue;
+                }
+
+                station
+                    .passengers
+                    .push(Passenger::new(target, station_id, now));
+            }
+        }
+    }
+
+    /// Unloads and boards passengers on every train that has just arrived, and sends the trains
+    /// that have waited long enough off to the next station. The more people get on and off, the
+    /// longer the train stays. Returns the passengers that have reached their destination.
+    pub fn serve_stopped_trains(
+        &mut self,
+        now: f32,
+        delta_secs: f32,
+    ) -> Vec<(StationId, Passenger)> {
+        let mut delivered = Vec::new();
+
+        for train_index in 0..self.trains.len() {
+            let train = &mut self.trains[train_index];
+            if !train.stopped {
+                continue;
+            }
+
+            match train.dwell {
+                Some(remaining) if remaining > delta_secs => {
+                    train.dwell = Some(remaining - delta_secs);
+                    continue;
+                }
+                Some(_) => {
+                    let (line_id, last_station, station_id) =
+                        (train.line_id, train.last_station, train.next_station);
+                    let next_stop = self.next_stop(line_id, last_station, station_id);
+
+                    let train = &mut self.trains[train_index];
+                    train.dwell = None;
+                    if let Some(next_stop) = next_stop {
+                        train.last_station = station_id;
+                        train.next_station = next_stop;
+                        train.traveled_distance = 0.;
+                        train.stopped = false;
+                    }
+                    continue;
+                }
+                None => {}
+            }
+
+            let train = &self.trains[train_index];
+
+            let line_id = train.line_id;
+            let station_id = train.next_station;
+            let next_stop = self.next_stop(line_id, train.last_station, station_id);
+            let station_kind = self.stations[station_id].kind;
+
+            // Whether riding on to `next_stop` gets the passenger closer to their target.
+            let should_ride = |passenger: &Passenger| {
+                next_stop.is_some_and(|next_stop| {
+                    self.distance_to_kind(next_stop, passenger.target)
+                        < self.distance_to_kind(station_id, passenger.target)
+                })
+            };
+
+            let (staying, leaving): (Vec<Passenger>, Vec<Passenger>) = self.trains[train_index]
+                .passengers
+                .iter()
+                .partition(|passenger| passenger.target != station_kind && should_ride(passenger));
+
+            let free_seats = self.trains[train_index]
+                .capacity()
+                .saturating_sub(staying.len());
+            let (boarding, waiting): (Vec<Passenger>, Vec<Passenger>) = self.stations[station_id]
+                .passengers
+                .iter()
+                .partition(|passenger| should_ride(passenger));
+            let (boarding, waiting) = if boarding.len() > free_seats {
+                let (boarding, left_behind) = boarding.split_at(free_seats);
+                (boarding.to_vec(), [waiting, left_behind.to_vec()].concat())
+            } else {
+                (boarding, waiting)
+            };
+
+            let exchanged = leaving.len() + boarding.len();
+
+            let mut station_passengers = waiting;
+            for mut passenger in leaving {
+                passenger.alight(now);
+
+                if passenger.target == station_kind {
+                    delivered.push((station_id, passenger));
+                } else {
+                    station_passengers.push(passenger);
+                }
+            }
+            self.stations[station_id].passengers = station_passengers;
+
+            let train = &mut self.trains[train_index];
+            train.passengers = staying;
+            for mut passenger in boarding {
+                passenger.board(line_id, now);
+                train.passengers.push(passenger);
+            }
+
+            train.dwell = Some(BASE_DWELL_TIME + exchanged as f32 * DWELL_TIME_PER_PASSENGER);
+        }
+
+        delivered
+    }
+
+    /// Removes the waiting passengers who have run out of patience.
+    pub fn remove_impatient_passengers(&mut self, now: f32) -> Vec<(StationId, Passenger)> {
+        let mut abandoned = Vec::new();
+
+        for (station_id, station) in self.stations.iter_mut().enumerate() {
+            station.passengers.retain(|passenger| {
+                if passenger.is_out_of_patience(now) {
+                    abandoned.push((station_id, *passenger));
+                    false
+                } else {
+                    true
+                }
+            });
+        }
+
+        abandoned
+    }
+
+    /// Advances the overcrowding timers. Returns a station that has been overcrowded for too long,
+    /// if there is one.
+    pub fn update_overcrowding(&mut self, delta_minutes: f32) -> Option<StationId> {
         for station in &mut self.stations {
-            if rng.random::<f32>() < station.intensity {
-                station.passengers.push(Passenger {
-                    target: match rng.random_range(0..3) {
-                        0 => 2,
-                        1 => 1,
-                        _ => 0,
-                    },
-                });
+            if station.passengers.len() > STATION_CAPACITY {
+                station.overcrowded_for += delta_minutes;
+            } else {
+                station.overcrowded_for = 0.;
             }
         }
+
+        self.stations
+            .iter()
+            .position(|station| station.overcrowded_for > OVERCROWDING_LIMIT)
     }
 
     fn calculate_distances(&mut self) {
@@ -235,7 +1067,13 @@ impl Metro {
         self.distances = vec![vec![f32::INFINITY; station_count]; station_count];
 
         for (i, connections) in self.connections.iter().enumerate() {
+            self.distances[i][i] = 0.;
+
             for (j, connection) in connections.iter().enumerate() {
+                if connection.is_empty() {
+                    continue;
+                }
+
                 let from_station = &self.stations[i];
                 let to_station = &self.stations[j];
 
@@ -246,12 +1084,12 @@ impl Metro {
         for k in 0..station_count {
             for i in 0..station_count {
                 for j in 0..station_count {
-                    if self.distances[k][i] != f32::INFINITY
+                    if self.distances[i][k] != f32::INFINITY
                         && self.distances[k][j] != f32::INFINITY
                     {
                         self.distances[i][j] = f32::min(
                             self.distances[i][j],
-                            self.distances[k][i] + self.distances[k][j],
+                            self.distances[i][k] + self.distances[k][j],
                         );
                     }
                 }
@@ -259,90 +1097,47 @@ impl Metro {
         }
     }
 
-    pub fn move_trains(&mut self, delta_distance: f32) {
-        for train in &mut self.trains {
+    /// Moves the trains along their paths, speeding up after leaving a station and braking before
+    /// the next one.
+    pub fn move_trains(&mut self, delta_secs: f32) {
+        for train_index in 0..self.trains.len() {
+            let train = &self.trains[train_index];
             if train.stopped {
                 continue;
             }
 
-            train.traveled_distance += delta_distance;
+            let total_distance = self.train_path(train).length();
+
+            let train = &mut self.trains[train_index];
+            let remaining_distance = total_distance - train.traveled_distance;
+            let braking_distance = train.speed * train.speed / (2. * TRAIN_ACCELERATION);
 
-            let last_station = &self.stations[train.last_station];
-            let next_station = &self.stations[train.next_station];
-            let total_distance = last_station.position.distance(next_station.position);
+            train.speed = if remaining_distance <= braking_distance {
+                (2. * TRAIN_ACCELERATION * remaining_distance)
+                    .sqrt()
+                    .max(TRAIN_MIN_SPEED)
+            } else {
+                (train.speed + TRAIN_ACCELERATION * delta_secs).min(TRAIN_SPEED)
+            };
+            train.traveled_distance += train.speed * delta_secs;
 
             if train.traveled_distance >= total_distance {
                 train.traveled_distance = total_distance;
+                train.speed = 0.;
                 train.stopped = true;
             }
         }
     }
-
-    // Onboards *one* passenger on each stopped train. The idea is to run this multiple times with
-    // a delay (For gameplay purposes) until all the necessary passengers have settled.
-    // If there are no passengers left to onboard, updates the train to indicate that it should
-    // start moving.
-    // pub fn onboard_passengers(&mut self) {
-    //     for train in &mut self.trains {
-    //         if train.stopped {
-    //             let curr_station_id = train.next_station;
-    //
-    //             let next_station_candidates = self.connections[train.next_station]
-    //                 .iter()
-    //                 .filter(|connection| connection.line_id == curr_station_id)
-    //                 .collect::<Vec<&Connection>>();
-    //
-    //             let next_station = match next_station_candidates[..] {
-    //                 // --(a)----(curr)-|
-    //                 // The train arrived at the end of the line and should turn around.
-    //                 [a] => a,
-    //                 // --(a)----(curr)----(b)--
-    //                 // There's another station ahead of the train.
-    //                 [a, b] => {
-    //                     if a.target == train.last_station {
-    //                         b
-    //                     } else {
-    //                         a
-    //                     }
-    //                 }
-    //                 _ => panic!("A single line shouldn't branch into multiple directinos."),
-    //             };
-    //
-    //             let next_passenger_to_board = self.stations[curr_station_id]
-    //                 .passengers
-    //                 .iter()
-    //                 .position(|passenger| {
-    //                     (0..self.distances[curr_station_id].len())
-    //                         .find(|final_station_id_candidate| {
-    //                             self.stations[*final_station_id_candidate].kind == passenger.target
-    //                         })
-    //                         .map(|final_station_id| final_station_id as StationId)
-    //                         .filter(|final_station_id| {
-    //                             self.distances[curr_station_id][*final_station_id]
-    //                                 > self.distances[next_station.target][*final_station_id]
-    //                         })
-    //                         .is_some()
-    //                 });
-    //
-    //             if let Some(i) = next_passenger_to_board {
-    //                 let passenger = self.stations[curr_station_id].passengers.remove(i);
-    //
-    //                 train.passengers.push(passenger);
-    //             } else {
-    //                 train.stopped = false;
-    //                 train.next_station = next_station.target;
-    //                 train.last_station = curr_station_id;
-    //             }
-    //
-    //             return;
-    //         }
-    //     }
-    // }
 }
 
 #[derive(Resource)]
 pub struct MetroResources {
+    /// Trains the player owns, including the ones running on lines.
     pub total_trains: usize,
+    /// Carriages the player owns, including the ones attached to trains.
+    pub total_carriages: usize,
+    /// Tunnels left for crossing water. Nothing to cross yet, so they're never spent.
+    pub tunnels: usize,
     pub available_lines: usize,
     pub max_lines: usize,
 }
@@ -351,8 +1146,73 @@ impl MetroResources {
     pub fn new() -> Self {
         Self {
             total_trains: 3,
+            total_carriages: 2,
+            tunnels: 3,
             available_lines: 3,
             max_lines: 9,
         }
     }
+
+    /// Trains left in the inventory.
+    pub fn available_trains(&self, metro: &Metro) -> usize {
+        self.total_trains.saturating_sub(metro.trains.len())
+    }
+
+    /// Carriages left in the inventory.
+    pub fn available_carriages(&self, metro: &Metro) -> usize {
+        let used = metro
+            .trains
+            .iter()
+            .map(|train| train.carriage_count)
+            .sum::<usize>();
+        self.total_carriages.saturating_sub(used)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn time_on_trains_doesnt_use_up_patience() {
+        let mut passenger = Passenger::new(0, 0, 0.);
+        passenger.board(0, 10.);
+        passenger.alight(10. + PASSENGER_PATIENCE);
+
+        assert!(!passenger.is_out_of_patience(20. + PASSENGER_PATIENCE));
+        assert!(passenger.is_out_of_patience(15. + 2. * PASSENGER_PATIENCE));
+    }
+    #[test]
+    fn removing_a_stopped_train_unloads_it_at_its_station() {
+        let mut metro = Metro::new();
+        metro.set_line_path(0, vec![0, 1]);
+
+        let mut passengers = vec![Passenger::new(1, 0, 0.), Passenger::new(2, 0, 0.)];
+        for passenger in &mut passengers {
+            passenger.board(0, 0.);
+        }
+        let train_id = metro.new_train_id();
+        metro.trains.push(Train {
+            passengers,
+            next_station: 1,
+            stopped: true,
+            ..Train::new(train_id, 0, 0)
+        });
+
+        metro.remove_train(0, 10.);
+
+        let delivered = metro
+            .removed_train_deliveries
+            .iter()
+            .map(|(station_id, passenger, _)| (*station_id, passenger.target))
+            .collect::<Vec<_>>();
+        assert_eq!(delivered, [(1, 1)]);
+        let waiting = metro.stations[1]
+            .passengers
+            .iter()
+            .map(|passenger| passenger.target)
+            .collect::<Vec<_>>();
+        assert_eq!(waiting, [2]);
+        assert!(metro.stations[0].passengers.is_empty());
+    }
 }
@@ -1,20 +1,26 @@
-use bevy::{
-    math::VectorSpace,
-    prelude::*,
-    window::{PrimaryWindow, WindowResized},
-};
+use bevy::{math::VectorSpace, prelude::*};
 use lines::{LineDragHandle, StationLineDragTarget};
-use metro::{LineId, MAP_SIZE, Metro, MetroResources, StationId};
+use metro::{LineId, Metro, MetroResources, StationId};
 use utils::STATION_MESHES;
 
 use crate::AppState;
 use bevy::color::palettes::basic as colors;
 
+pub mod camera;
+pub mod clock;
 pub mod events;
+pub mod export;
+pub mod gamepad;
+pub mod geometry;
+pub mod history;
 pub mod lines;
+pub mod lines_keyboard;
 pub mod lines_visual;
 pub mod metro;
+pub mod satisfaction;
 pub mod stations;
+pub mod statistics;
+pub mod trains;
 pub mod ui;
 pub mod utils;
 
@@ -25,20 +31,24 @@ pub struct BestScore(pub u32);
 pub struct GameComponent;
 
 pub(super) fn plugin(app: &mut App) {
-    app.add_plugins(lines::plugin)
+    app.add_plugins(camera::plugin)
+        .add_plugins(clock::plugin)
+        .add_plugins(lines::plugin)
+        .add_plugins(lines_keyboard::plugin)
         .add_plugins(lines_visual::plugin)
         .add_plugins(ui::plugin)
         .add_plugins(stations::plugin)
         .add_plugins(metro::plugin)
         .add_plugins(events::plugin)
+        .add_plugins(satisfaction::plugin)
+        .add_plugins(statistics::plugin)
+        .add_plugins(export::plugin)
+        .add_plugins(gamepad::plugin)
+        .add_plugins(trains::plugin)
+        .add_plugins(history::plugin)
         .insert_resource(BestScore(0))
-        .add_systems(OnEnter(AppState::Game), (setup_scene, scale_view).chain())
-        .add_systems(
-            Update,
-            scale_view
-                .run_if(in_state(AppState::Game))
-                .run_if(on_event::<WindowResized>),
-        );
+        .add_systems(OnEnter(AppState::Game), setup_scene)
+        .add_systems(OnExit(AppState::Game), clean_up_game);
 }
 
 fn setup_scene(
@@ -69,20 +79,8 @@ fn setup_scene(
     ));
 }
 
-fn scale_view(
-    mut projection: Single<&mut Projection, With<Camera2d>>,
-    window: Single<&Window, With<PrimaryWindow>>,
-) {
-    let window_size = window.size();
-
-    let scale_factor = if MAP_SIZE.x / MAP_SIZE.y > window_size.x / window_size.y {
-        MAP_SIZE.x / window_size.x
-    } else {
-        MAP_SIZE.y / window_size.y
-    };
-
-    **projection = Projection::Orthographic(OrthographicProjection {
-        scale: scale_factor,
-        ..OrthographicProjection::default_2d()
-    });
+fn clean_up_game(mut commands: Commands, game_components_q: Query<Entity, With<GameComponent>>) {
+    for entity in game_components_q.iter() {
+        commands.entity(entity).despawn();
+    }
 }
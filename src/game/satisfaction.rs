@@ -0,0 +1,78 @@
+use bevy::prelude::*;
+
+use crate::AppState;
+
+use super::{
+    BestScore,
+    events::{PassengerAbandoned, PassengerDelivered},
+    metro::{PASSENGER_PATIENCE, Passenger},
+};
+
+/// How much a single transfer lowers the rating of a journey.
+const TRANSFER_PENALTY: f32 = 0.1;
+/// How much waiting for the whole `PASSENGER_PATIENCE` lowers the rating of a journey.
+const WAIT_PENALTY: f32 = 0.5;
+/// Even the worst completed journey is better than not getting there at all.
+const MIN_DELIVERED_RATING: f32 = 0.1;
+
+pub(super) fn plugin(app: &mut App) {
+    app.insert_resource(Satisfaction::default())
+        .add_systems(OnEnter(AppState::Game), reset_satisfaction)
+        .add_systems(OnExit(AppState::Game), update_best_score)
+        .add_systems(
+            Update,
+            record_passenger_outcomes.run_if(in_state(AppState::Game)),
+        );
+}
+
+/// How happy the passengers are with the network.
+#[derive(Resource, Default)]
+pub struct Satisfaction {
+    pub delivered: u32,
+    pub abandoned: u32,
+    total_rating: f32,
+}
+
+impl Satisfaction {
+    /// From 0.0 to 1.0. Everybody is happy before the first passenger has finished their journey.
+    pub fn score(&self) -> f32 {
+        let journeys = self.delivered + self.abandoned;
+
+        if journeys == 0 {
+            1.0
+        } else {
+            self.total_rating / journeys as f32
+        }
+    }
+}
+
+/// From 0.0 to 1.0. Long waits and transfers make the passenger less happy.
+pub fn rate_journey(passenger: &Passenger, now: f32) -> f32 {
+    let wait_ratio = (passenger.total_wait(now) / PASSENGER_PATIENCE).clamp(0.0, 1.0);
+
+    (1.0 - wait_ratio * WAIT_PENALTY - passenger.transfers as f32 * TRANSFER_PENALTY)
+        .clamp(MIN_DELIVERED_RATING, 1.0)
+}
+
+fn reset_satisfaction(mut satisfaction: ResMut<Satisfaction>) {
+    *satisfaction = Satisfaction::default();
+}
+
+fn record_passenger_outcomes(
+    mut delivered_events: EventReader<PassengerDelivered>,
+    mut abandoned_events: EventReader<PassengerAbandoned>,
+    mut satisfaction: ResMut<Satisfaction>,
+) {
+    for event in delivered_events.read() {
+        satisfaction.delivered += 1;
+        satisfaction.total_rating += rate_journey(&event.passenger, event.time);
+    }
+
+    for _ in abandoned_events.read() {
+        satisfaction.abandoned += 1;
+    }
+}
+
+fn update_best_score(satisfaction: Res<Satisfaction>, mut best_score: ResMut<BestScore>) {
+    best_score.0 = best_score.0.max(satisfaction.delivered);
+}
@@ -1,6 +1,6 @@
 use bevy::ecs::spawn::SpawnRelatedBundle;
+use bevy::platform::collections::HashMap;
 use bevy::prelude::*;
-use bevy::{color::palettes::basic as colors, platform::collections::HashMap};
 use std::f32::consts::SQRT_2;
 
 use crate::{
@@ -12,21 +12,104 @@ use crate::{
     },
 };
 
-const STATION_MESH_RADIUS: f32 = 4.;
+pub(super) const STATION_MESH_RADIUS: f32 = 4.;
+/// How much bigger a station gets while the line being drawn is over it.
+const DRAG_HOVER_SCALE: f32 = 1.3;
+/// Passenger icons are the station shapes scaled down by this much.
+pub(super) const PASSENGER_ICON_SCALE: f32 = 0.3;
+pub(super) const PASSENGER_ICON_SPACING: f32 = 2.6;
+/// How many waiting passengers are shown in a row next to the station.
+const WAITING_PASSENGERS_PER_ROW: usize = 6;
+/// Distance (in logical pixels) between the cursor and the top left corner of the tooltip.
+const TOOLTIP_OFFSET: Vec2 = Vec2::new(16., 16.);
+/// How long (in real seconds) a finger has to rest on a station to show its tooltip.
+const LONG_PRESS_DURATION: f32 = 0.5;
+/// How far (in logical pixels) a finger can move before the long press turns into a drag.
+const LONG_PRESS_TOLERANCE: f32 = 10.;
 
 use super::{
     GameComponent,
-    metro::{Metro, StationId, StationKind},
+    clock::GameClock,
+    events::LineDragHoversStation,
+    metro::{Metro, OVERCROWDING_LIMIT, STATION_CAPACITY, StationId, StationKind},
+    statistics::Statistics,
 };
+use crate::style::{Theme, ThemeColor, ThemedBackground, ThemedText, place_node};
 
 pub(super) fn plugin(app: &mut App) {
     app.add_systems(
         OnEnter(AppState::Game),
         (create_station_meshes, setup_scene).chain(),
     )
+    .add_systems(OnEnter(AppState::Game), setup_station_tooltip)
+    .add_systems(
+        Update,
+        (
+            update_waiting_passengers,
+            (show_long_pressed_station, update_station_tooltip).chain(),
+            show_line_drag_hover.run_if(on_event::<LineDragHoversStation>),
+            apply_station_theme.run_if(resource_changed::<Theme>),
+        )
+            .run_if(in_state(AppState::Game)),
+    )
     .add_observer(on_station_spawned);
 }
 
+/// The station under the pointer, and where the pointer is on the screen.
+#[derive(Component)]
+struct StationTooltip {
+    hovered: Option<(StationId, Vec2)>,
+    /// Touches don't hover, so a finger has to rest on the station for a while instead. The station,
+    /// where the finger went down, and when (in real seconds).
+    pressed: Option<(StationId, Vec2, f32)>,
+}
+
+#[derive(Component)]
+struct StationTooltipText;
+
+/// The passengers currently shown by the entity's children, so that they're only respawned when
+/// something changes.
+#[derive(Component, Default)]
+pub(super) struct PassengerIcons(pub Vec<StationKind>);
+
+#[derive(Component)]
+struct WaitingPassengersVisual {
+    station_id: StationId,
+}
+
+/// Replaces the children of `entity` with a grid of passenger icons, filled row by row.
+pub(super) fn spawn_passenger_icons(
+    commands: &mut Commands,
+    entity: Entity,
+    kinds: &[StationKind],
+    columns: usize,
+    origin: Vec2,
+    station_meshes: &StationMeshes,
+    material: &Handle<ColorMaterial>,
+) {
+    commands
+        .entity(entity)
+        .despawn_related::<Children>()
+        .with_children(|parent| {
+            for (i, kind) in kinds.iter().enumerate() {
+                let Some(mesh) = station_meshes.meshes.get(kind) else {
+                    continue;
+                };
+
+                let column = (i % columns) as f32;
+                let row = (i / columns) as f32;
+                let position = origin + Vec2::new(column, -row) * PASSENGER_ICON_SPACING;
+
+                parent.spawn((
+                    Mesh2d(mesh.clone()),
+                    MeshMaterial2d(material.clone()),
+                    Transform::from_translation(position.extend(0.1))
+                        .with_scale(Vec3::splat(PASSENGER_ICON_SCALE)),
+                ));
+            }
+        });
+}
+
 #[derive(Component)]
 struct StationComponent {
     station_id: StationId,
@@ -40,10 +123,9 @@ fn on_station_spawned(
     // station: Station,
     station_meshes: Res<StationMeshes>,
     mut materials: ResMut<Assets<ColorMaterial>>,
+    theme: Res<Theme>,
 ) {
     const BORDER_SCALE: f32 = 1.2;
-    const INNER_COLOR: Srgba = colors::GRAY;
-    const BORDER_COLOR: Srgba = colors::WHITE;
 
     let Ok(station_component) = station_component_q.get(trigger.target()) else {
         return;
@@ -51,48 +133,359 @@ fn on_station_spawned(
 
     commands.spawn((
         GameComponent,
-        LineDragHandle::New {
-            station_id: station_component.station_id,
-        },
-        StationLineDragTarget {
+        WaitingPassengersVisual {
             station_id: station_component.station_id,
         },
-        Mesh2d(
-            station_meshes
-                .meshes
-                .get(&station_component.station_id)
-                .unwrap()
-                .clone(),
+        PassengerIcons::default(),
+        Transform::from_translation(
+            (station_component.position + Vec2::new(STATION_MESH_RADIUS * 1.8, 0.)).extend(1.0),
         ),
-        Transform::from_translation(station_component.position.extend(1.0)),
-        children![
-            (
-                Mesh2d(
-                    station_meshes
-                        .meshes
-                        .get(&station_component.station_id)
-                        .unwrap()
-                        .clone(),
-                ),
+        Visibility::default(),
+    ));
 
-                MeshMaterial2d(materials.add(Color::from(INNER_COLOR))),
-                Transform::from_translation(Vec3::new(0., 0., 1.)),
+    commands
+        .spawn((
+            GameComponent,
+            LineDragHandle::New {
+                station_id: station_component.station_id,
+            },
+            StationLineDragTarget {
+                station_id: station_component.station_id,
+            },
+            Mesh2d(
+                station_meshes
+                    .meshes
+                    .get(&station_component.station_id)
+                    .unwrap()
+                    .clone(),
             ),
-            (
-                Mesh2d(
-                    station_meshes
-                        .meshes
-                        .get(&station_component.station_id)
-                        .unwrap()
-                        .clone(),
+            Transform::from_translation(station_component.position.extend(1.0)),
+            HoverableStation {
+                station_id: station_component.station_id,
+            },
+            children![
+                (
+                    Mesh2d(
+                        station_meshes
+                            .meshes
+                            .get(&station_component.station_id)
+                            .unwrap()
+                            .clone(),
+                    ),
+                    StationInner,
+                    MeshMaterial2d(materials.add(Color::from(theme.station_fill))),
+                    Transform::from_translation(Vec3::new(0., 0., 1.)),
                 ),
-                MeshMaterial2d(materials.add(Color::from(BORDER_COLOR))),
-                Transform::from_translation(Vec3::ZERO).with_scale(Vec3::ONE * BORDER_SCALE),
-            )
-        ],
+                (
+                    Mesh2d(
+                        station_meshes
+                            .meshes
+                            .get(&station_component.station_id)
+                            .unwrap()
+                            .clone(),
+                    ),
+                    StationBorder,
+                    MeshMaterial2d(materials.add(Color::from(theme.station_border))),
+                    Transform::from_translation(Vec3::ZERO).with_scale(Vec3::ONE * BORDER_SCALE),
+                )
+            ],
+        ))
+        .observe(on_station_hovered)
+        .observe(on_station_hover_moved)
+        .observe(on_station_unhovered)
+        .observe(on_station_released);
+}
+
+#[derive(Component)]
+struct HoverableStation {
+    station_id: StationId,
+}
+
+/// The filled part of the station, inside the border.
+#[derive(Component)]
+struct StationInner;
+
+#[derive(Component)]
+struct StationBorder;
+
+/// Scales up the station the line being drawn is over, and tints it depending on whether the line
+/// can be connected to it.
+fn show_line_drag_hover(
+    mut hover_events: EventReader<LineDragHoversStation>,
+    mut stations_q: Query<(&HoverableStation, &mut Transform, &Children)>,
+    inner_q: Query<&MeshMaterial2d<ColorMaterial>, With<StationInner>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    theme: Res<Theme>,
+) {
+    for event in hover_events.read() {
+        let Some((_, mut transform, children)) = stations_q
+            .iter_mut()
+            .find(|(station, _, _)| station.station_id == event.station_id)
+        else {
+            continue;
+        };
+
+        let (scale, color) = match (event.hovering, event.valid) {
+            (false, _) => (1., theme.station_fill),
+            (true, true) => (DRAG_HOVER_SCALE, theme.primary),
+            (true, false) => (DRAG_HOVER_SCALE, theme.error),
+        };
+
+        transform.scale = Vec3::new(scale, scale, 1.);
+        for material in inner_q.iter_many(children) {
+            if let Some(material) = materials.get_mut(material.id()) {
+                material.color = color.into();
+            }
+        }
+    }
+}
+
+/// Recolours the stations and the waiting passengers when the theme changes.
+fn apply_station_theme(
+    inner_q: Query<&MeshMaterial2d<ColorMaterial>, With<StationInner>>,
+    borders_q: Query<&MeshMaterial2d<ColorMaterial>, With<StationBorder>>,
+    station_meshes: Res<StationMeshes>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    theme: Res<Theme>,
+) {
+    let mut recolor = |material: &Handle<ColorMaterial>, color: Srgba| {
+        if let Some(material) = materials.get_mut(material) {
+            material.color = color.into();
+        }
+    };
+
+    for material in &inner_q {
+        recolor(&material.0, theme.station_fill);
+    }
+    for material in &borders_q {
+        recolor(&material.0, theme.station_border);
+    }
+    recolor(&station_meshes.waiting_passenger_material, theme.passenger);
+}
+
+fn on_station_hovered(
+    trigger: Trigger<Pointer<Over>>,
+    stations_q: Query<&HoverableStation>,
+    mut tooltip: Single<&mut StationTooltip>,
+    time: Res<Time<Real>>,
+) {
+    let Ok(station) = stations_q.get(trigger.target()) else {
+        return;
+    };
+
+    let position = trigger.pointer_location.position;
+    if trigger.pointer_id.is_touch() {
+        tooltip.pressed = Some((station.station_id, position, time.elapsed_secs()));
+    } else {
+        tooltip.hovered = Some((station.station_id, position));
+    }
+}
+
+fn on_station_hover_moved(
+    trigger: Trigger<Pointer<Move>>,
+    stations_q: Query<&HoverableStation>,
+    mut tooltip: Single<&mut StationTooltip>,
+) {
+    let Ok(station) = stations_q.get(trigger.target()) else {
+        return;
+    };
+
+    let position = trigger.pointer_location.position;
+    if trigger.pointer_id.is_touch() {
+        if tooltip
+            .pressed
+            .is_some_and(|(_, start, _)| start.distance(position) > LONG_PRESS_TOLERANCE)
+        {
+            tooltip.pressed = None;
+        }
+        // The tooltip follows the finger once the long press has shown it.
+        if tooltip.hovered.is_none() {
+            return;
+        }
+    }
+
+    tooltip.hovered = Some((station.station_id, position));
+}
+
+fn on_station_unhovered(
+    trigger: Trigger<Pointer<Out>>,
+    stations_q: Query<&HoverableStation>,
+    mut tooltip: Single<&mut StationTooltip>,
+) {
+    let Ok(station) = stations_q.get(trigger.target()) else {
+        return;
+    };
+
+    if tooltip
+        .hovered
+        .is_some_and(|(station_id, _)| station_id == station.station_id)
+    {
+        tooltip.hovered = None;
+    }
+    if tooltip
+        .pressed
+        .is_some_and(|(station_id, _, _)| station_id == station.station_id)
+    {
+        tooltip.pressed = None;
+    }
+}
+
+/// Lifting a finger doesn't send `Out`, picking forgets about the touch right away. So the tooltip
+/// it opened is closed here instead.
+fn on_station_released(
+    trigger: Trigger<Pointer<Released>>,
+    mut tooltip: Single<&mut StationTooltip>,
+) {
+    if trigger.pointer_id.is_touch() {
+        tooltip.hovered = None;
+        tooltip.pressed = None;
+    }
+}
+
+fn show_long_pressed_station(mut tooltip: Single<&mut StationTooltip>, time: Res<Time<Real>>) {
+    let Some((station_id, position, pressed_at)) = tooltip.pressed else {
+        return;
+    };
+
+    if time.elapsed_secs() - pressed_at >= LONG_PRESS_DURATION {
+        tooltip.hovered = Some((station_id, position));
+        tooltip.pressed = None;
+    }
+}
+
+fn setup_station_tooltip(mut commands: Commands) {
+    commands.spawn((
+        GameComponent,
+        StationTooltip {
+            hovered: None,
+            pressed: None,
+        },
+        Node {
+            display: Display::None,
+            position_type: PositionType::Absolute,
+            padding: UiRect::all(Val::Px(8.)),
+            ..default()
+        },
+        ThemedBackground::new(ThemeColor::Surface).with_alpha(0.95),
+        BorderRadius::all(Val::Px(6.)),
+        Pickable::IGNORE,
+        children![(
+            StationTooltipText,
+            Text::new(""),
+            TextFont {
+                font_size: 14.0,
+                ..default()
+            },
+            ThemedText(ThemeColor::OnSurface),
+            Pickable::IGNORE,
+        )],
     ));
 }
-fn create_station_meshes(mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>) {
+
+fn update_station_tooltip(
+    mut tooltip_q: Single<(&StationTooltip, &mut Node)>,
+    mut tooltip_text: Single<&mut Text, With<StationTooltipText>>,
+    metro: Res<Metro>,
+    statistics: Res<Statistics>,
+    clock: Res<GameClock>,
+    ui_scale: Res<UiScale>,
+) {
+    let (tooltip, node) = &mut *tooltip_q;
+
+    let Some((station_id, cursor_position)) = tooltip.hovered else {
+        if node.display != Display::None {
+            node.display = Display::None;
+        }
+        return;
+    };
+
+    node.display = Display::Flex;
+    place_node(node, cursor_position, TOOLTIP_OFFSET, &ui_scale);
+
+    let text = station_tooltip_text(station_id, &metro, &statistics, &clock);
+    if tooltip_text.0 != text {
+        tooltip_text.0 = text;
+    }
+}
+
+fn station_tooltip_text(
+    station_id: StationId,
+    metro: &Metro,
+    statistics: &Statistics,
+    clock: &GameClock,
+) -> String {
+    let station = &metro.stations[station_id];
+
+    let mut waiting_by_kind = HashMap::<StationKind, usize>::new();
+    for passenger in &station.passengers {
+        *waiting_by_kind.entry(passenger.target).or_default() += 1;
+    }
+    let mut waiting_by_kind = waiting_by_kind.into_iter().collect::<Vec<_>>();
+    waiting_by_kind.sort();
+
+    let mut lines_serving = metro
+        .lines
+        .iter()
+        .filter(|(_, path)| path.contains(&station_id))
+        .map(|(line_id, _)| *line_id + 1)
+        .collect::<Vec<_>>();
+    lines_serving.sort();
+
+    let mut lines = vec![
+        format!(
+            "{} station ({:?})",
+            kind_name(station.kind),
+            station.district
+        ),
+        format!("{}/{} waiting", station.passengers.len(), STATION_CAPACITY),
+    ];
+
+    for (kind, count) in waiting_by_kind {
+        lines.push(format!("  {count} to a {}", kind_name(kind)));
+    }
+
+    if station.overcrowded_for > 0. {
+        lines.push(format!(
+            "Overcrowded for {:.0}/{:.0} min",
+            station.overcrowded_for, OVERCROWDING_LIMIT
+        ));
+    }
+
+    lines.push(if lines_serving.is_empty() {
+        "Not served by any line".to_string()
+    } else {
+        let lines_serving = lines_serving
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        format!("Lines {}", lines_serving.join(", "))
+    });
+
+    if !station.passengers.is_empty() {
+        let current_wait = station
+            .passengers
+            .iter()
+            .map(|passenger| passenger.current_wait(clock.minutes))
+            .sum::<f32>()
+            / station.passengers.len() as f32;
+        lines.push(format!("Waiting for {current_wait:.0} min on average"));
+    }
+
+    if let Some(origin) = statistics.origins.get(&station_id) {
+        lines.push(format!(
+            "Average wait of past journeys {:.0} min",
+            origin.wait_time().mean
+        ));
+    }
+
+    lines.join("\n")
+}
+fn create_station_meshes(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    theme: Res<Theme>,
+) {
     let mut mesh_map = HashMap::new();
     mesh_map.insert(0, Mesh::from(square()));
     mesh_map.insert(1, Mesh::from(triangle()));
@@ -103,9 +496,20 @@ fn create_station_meshes(mut commands: Commands, mut meshes: ResMut<Assets<Mesh>
             .iter()
             .map(|(station_kind, mesh)| (*station_kind, meshes.add(mesh.clone())))
             .collect(),
+        waiting_passenger_material: materials.add(Color::from(theme.passenger)),
     });
 }
 
+/// The name of the shape the station kind is drawn with, see `create_station_meshes`.
+pub fn kind_name(kind: StationKind) -> &'static str {
+    match kind {
+        0 => "square",
+        1 => "triangle",
+        2 => "circle",
+        _ => "unknown",
+    }
+}
+
 fn circle() -> Circle {
     Circle::new(STATION_MESH_RADIUS)
 }
@@ -136,8 +540,42 @@ fn square() -> Rectangle {
 }
 
 #[derive(Resource)]
-struct StationMeshes {
-    meshes: HashMap<StationKind, Handle<Mesh>>,
+pub(super) struct StationMeshes {
+    pub meshes: HashMap<StationKind, Handle<Mesh>>,
+    waiting_passenger_material: Handle<ColorMaterial>,
+}
+
+fn update_waiting_passengers(
+    mut commands: Commands,
+    mut visuals_q: Query<(Entity, &WaitingPassengersVisual, &mut PassengerIcons)>,
+    station_meshes: Res<StationMeshes>,
+    metro: Res<Metro>,
+) {
+    for (entity, visual, mut icons) in &mut visuals_q {
+        let Some(station) = metro.stations.get(visual.station_id) else {
+            continue;
+        };
+
+        let kinds = station
+            .passengers
+            .iter()
+            .map(|passenger| passenger.target)
+            .collect::<Vec<_>>();
+        if icons.0 == kinds {
+            continue;
+        }
+
+        spawn_passenger_icons(
+            &mut commands,
+            entity,
+            &kinds,
+            WAITING_PASSENGERS_PER_ROW,
+            Vec2::ZERO,
+            &station_meshes,
+            &station_meshes.waiting_passenger_material,
+        );
+        icons.0 = kinds;
+    }
 }
 
 fn setup_scene(mut commands: Commands, metro: Res<Metro>) {
@@ -148,3 +586,83 @@ fn setup_scene(mut commands: Commands, metro: Res<Metro>) {
         });
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use bevy::input::touch::TouchPhase;
+
+    use super::*;
+    use crate::testing::{FRAME_TIME, PickedEntity, headless_app, touch};
+
+    /// A station that's under every pointer, and its tooltip.
+    fn tooltip_app() -> App {
+        let mut app = headless_app();
+        app.insert_resource(Metro::new())
+            .init_resource::<Statistics>()
+            .insert_resource(GameClock::new())
+            .add_systems(OnEnter(AppState::Game), setup_station_tooltip)
+            .add_systems(
+                Update,
+                (show_long_pressed_station, update_station_tooltip).chain(),
+            );
+
+        let station = app
+            .world_mut()
+            .spawn(HoverableStation { station_id: 0 })
+            .observe(on_station_hovered)
+            .observe(on_station_hover_moved)
+            .observe(on_station_unhovered)
+            .observe(on_station_released)
+            .id();
+        app.world_mut().resource_mut::<PickedEntity>().0 = Some(station);
+        app.update();
+
+        app
+    }
+
+    fn tooltip_display(app: &mut App) -> Display {
+        let world = app.world_mut();
+        world
+            .query_filtered::<&Node, With<StationTooltip>>()
+            .single(world)
+            .unwrap()
+            .display
+    }
+
+    #[test]
+    fn long_press_shows_the_station_tooltip() {
+        let mut app = tooltip_app();
+
+        let position = Vec2::new(300., 300.);
+        touch(&mut app, 0, TouchPhase::Started, position);
+        app.update();
+        assert_eq!(tooltip_display(&mut app), Display::None);
+
+        // Moving the finger a little doesn't turn the press into a drag.
+        touch(&mut app, 0, TouchPhase::Moved, position + Vec2::splat(2.));
+        let frames = (LONG_PRESS_DURATION / FRAME_TIME.as_secs_f32()).ceil() as usize;
+        for _ in 0..frames {
+            app.update();
+        }
+        assert_eq!(tooltip_display(&mut app), Display::Flex);
+
+        touch(&mut app, 0, TouchPhase::Ended, position);
+        app.update();
+        app.update();
+        assert_eq!(tooltip_display(&mut app), Display::None);
+    }
+
+    #[test]
+    fn short_tap_doesnt_show_the_station_tooltip() {
+        let mut app = tooltip_app();
+
+        let position = Vec2::new(300., 300.);
+        touch(&mut app, 0, TouchPhase::Started, position);
+        app.update();
+        touch(&mut app, 0, TouchPhase::Ended, position);
+        for _ in 0..10 {
+            app.update();
+        }
+        assert_eq!(tooltip_display(&mut app), Display::None);
+    }
+}
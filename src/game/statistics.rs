@@ -0,0 +1,212 @@
+use bevy::{platform::collections::HashMap, prelude::*};
+
+use crate::AppState;
+
+use super::{
+    clock::GameClock,
+    events::{PassengerAbandoned, PassengerDelivered},
+    metro::{LineId, Passenger, StationId},
+};
+
+pub(super) fn plugin(app: &mut App) {
+    app.insert_resource(Statistics::default())
+        .add_systems(OnEnter(AppState::Game), reset_statistics)
+        .add_systems(Update, record_journeys.run_if(in_state(AppState::Game)));
+}
+
+/// A finished passenger journey, whether it was completed or not.
+#[derive(Clone)]
+pub struct Journey {
+    pub origin: StationId,
+    /// Where the passenger got off, or gave up.
+    pub destination: StationId,
+    pub lines_used: Vec<LineId>,
+    pub transfers: u32,
+    // All the times are in in-game minutes.
+    pub wait_time: f32,
+    pub travel_time: f32,
+    pub finished_at: f32,
+    pub completed: bool,
+}
+
+impl Journey {
+    fn new(passenger: &Passenger, destination: StationId, now: f32, completed: bool) -> Self {
+        Journey {
+            origin: passenger.origin,
+            destination,
+            lines_used: passenger.lines_used().collect(),
+            transfers: passenger.transfers,
+            wait_time: passenger.total_wait(now),
+            travel_time: passenger.ride_time,
+            finished_at: now,
+            completed,
+        }
+    }
+}
+
+/// Width (in in-game minutes) of the histogram bins the percentiles are read from.
+const HISTOGRAM_BIN: f32 = 1.;
+/// Longer samples all go into the last bin. A day is longer than any wait or ride.
+const HISTOGRAM_BINS: usize = 24 * 60;
+
+/// Count, mean and percentiles of a set of samples.
+#[derive(Clone, Copy, Default, Debug)]
+pub struct Summary {
+    pub count: usize,
+    pub mean: f32,
+    pub p50: f32,
+    pub p90: f32,
+    pub max: f32,
+}
+
+/// Running totals and a histogram of a set of samples. Takes the same memory however many samples
+/// are recorded, and the percentiles are accurate to `HISTOGRAM_BIN`.
+#[derive(Clone, Default)]
+pub struct Samples {
+    count: usize,
+    sum: f32,
+    max: f32,
+    histogram: Vec<u32>,
+}
+
+impl Samples {
+    fn push(&mut self, sample: f32) {
+        let sample = sample.max(0.);
+        let bin = ((sample / HISTOGRAM_BIN) as usize).min(HISTOGRAM_BINS - 1);
+        if self.histogram.len() <= bin {
+            self.histogram.resize(bin + 1, 0);
+        }
+
+        self.histogram[bin] += 1;
+        self.count += 1;
+        self.sum += sample;
+        self.max = self.max.max(sample);
+    }
+
+    pub fn summary(&self) -> Summary {
+        if self.count == 0 {
+            return Summary::default();
+        }
+
+        let percentile = |p: f32| {
+            let rank = ((self.count - 1) as f32 * p).round() as usize;
+            let mut seen = 0;
+            let bin = self
+                .histogram
+                .iter()
+                .position(|count| {
+                    seen += *count as usize;
+                    seen > rank
+                })
+                .unwrap_or(self.histogram.len() - 1);
+
+            ((bin as f32 + 0.5) * HISTOGRAM_BIN).min(self.max)
+        };
+
+        Summary {
+            count: self.count,
+            mean: self.sum / self.count as f32,
+            p50: percentile(0.5),
+            p90: percentile(0.9),
+            max: self.max,
+        }
+    }
+}
+
+/// Samples collected for a line, a station or a week.
+#[derive(Clone, Default)]
+pub struct Aggregate {
+    pub completed: u32,
+    pub abandoned: u32,
+    wait_times: Samples,
+    travel_times: Samples,
+}
+
+impl Aggregate {
+    fn record(&mut self, journey: &Journey) {
+        if journey.completed {
+            self.completed += 1;
+            self.travel_times.push(journey.travel_time);
+        } else {
+            self.abandoned += 1;
+        }
+
+        self.wait_times.push(journey.wait_time);
+    }
+
+    pub fn wait_time(&self) -> Summary {
+        self.wait_times.summary()
+    }
+
+    pub fn travel_time(&self) -> Summary {
+        self.travel_times.summary()
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct Statistics {
+    /// Every journey so far.
+    pub total: Aggregate,
+    /// Journeys that used the line at least once.
+    pub lines: HashMap<LineId, Aggregate>,
+    /// Journeys that started at the station.
+    pub origins: HashMap<StationId, Aggregate>,
+    /// Journeys that ended at the station.
+    pub destinations: HashMap<StationId, Aggregate>,
+    /// Indexed by `GameClock::week`.
+    pub weeks: Vec<Aggregate>,
+}
+
+impl Statistics {
+    pub fn record(&mut self, journey: Journey) {
+        self.total.record(&journey);
+        for line_id in &journey.lines_used {
+            self.lines.entry(*line_id).or_default().record(&journey);
+        }
+        self.origins
+            .entry(journey.origin)
+            .or_default()
+            .record(&journey);
+        self.destinations
+            .entry(journey.destination)
+            .or_default()
+            .record(&journey);
+
+        let week = GameClock {
+            minutes: journey.finished_at,
+        }
+        .week() as usize;
+        if self.weeks.len() <= week {
+            self.weeks.resize_with(week + 1, Aggregate::default);
+        }
+        self.weeks[week].record(&journey);
+    }
+}
+
+fn reset_statistics(mut statistics: ResMut<Statistics>) {
+    *statistics = Statistics::default();
+}
+
+fn record_journeys(
+    mut delivered_events: EventReader<PassengerDelivered>,
+    mut abandoned_events: EventReader<PassengerAbandoned>,
+    mut statistics: ResMut<Statistics>,
+) {
+    for event in delivered_events.read() {
+        statistics.record(Journey::new(
+            &event.passenger,
+            event.station_id,
+            event.time,
+            true,
+        ));
+    }
+
+    for event in abandoned_events.read() {
+        statistics.record(Journey::new(
+            &event.passenger,
+            event.station_id,
+            event.time,
+            false,
+        ));
+    }
+}
@@ -0,0 +1,248 @@
+use bevy::{platform::collections::HashSet, prelude::*};
+
+use crate::{AppState, settings::Preferences};
+
+use super::{
+    GameComponent,
+    metro::{Metro, Train, TrainId},
+    stations::{PASSENGER_ICON_SPACING, PassengerIcons, StationMeshes, spawn_passenger_icons},
+    ui,
+};
+
+const TRAIN_LENGTH: f32 = 9.;
+const TRAIN_WIDTH: f32 = 5.5;
+/// Trains are drawn on top of lines and stations.
+const TRAIN_Z: f32 = 3.;
+/// Passengers sit in rows of this many along the width of the train.
+const PASSENGER_ROWS: usize = 2;
+
+pub(super) fn plugin(app: &mut App) {
+    app.add_systems(OnEnter(AppState::Game), create_train_assets)
+        .add_systems(
+            Update,
+            (
+                sync_trains,
+                update_train_transforms,
+                update_train_passengers,
+            )
+                .chain()
+                .run_if(in_state(AppState::Game)),
+        );
+}
+
+/// The visual of the train with the id `train_id`. Trains are looked up by id, because removing
+/// a train shifts the indices of the ones after it.
+#[derive(Component)]
+pub struct TrainVisual {
+    pub train_id: TrainId,
+}
+
+/// The coloured body of the train. Scaled to the length of the train, so it's kept separate from the
+/// passenger icons.
+#[derive(Component)]
+struct TrainBody;
+
+#[derive(Component)]
+struct TrainPassengerGrid;
+
+#[derive(Resource)]
+struct TrainAssets {
+    body: Handle<Mesh>,
+    passenger_material: Handle<ColorMaterial>,
+}
+
+fn create_train_assets(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    commands.insert_resource(TrainAssets {
+        body: meshes.add(Rectangle::new(1., 1.)),
+        passenger_material: materials.add(Color::from(Srgba::gray(0.15))),
+    });
+}
+
+fn train_length(train: &Train) -> f32 {
+    TRAIN_LENGTH * (train.locomotive_count + train.carriage_count).max(1) as f32
+}
+
+/// Spawns and despawns train entities so that there's one for every train in `Metro`.
+fn sync_trains(
+    mut commands: Commands,
+    trains_q: Query<(Entity, &TrainVisual)>,
+    metro: Res<Metro>,
+    train_assets: Res<TrainAssets>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    preferences: Res<Preferences>,
+) {
+    let mut existing = HashSet::new();
+
+    for (entity, visual) in &trains_q {
+        if metro.train(visual.train_id).is_some() {
+            existing.insert(visual.train_id);
+        } else {
+            commands.entity(entity).despawn();
+        }
+    }
+
+    for train in &metro.trains {
+        if existing.contains(&train.id) {
+            continue;
+        }
+
+        commands
+            .spawn((
+                GameComponent,
+                TrainVisual { train_id: train.id },
+                Transform::from_translation(Vec3::Z * TRAIN_Z),
+                Visibility::default(),
+                children![
+                    (
+                        TrainBody,
+                        Mesh2d(train_assets.body.clone()),
+                        MeshMaterial2d(
+                            materials.add(Color::from(preferences.line_color(train.line_id))),
+                        ),
+                        Transform::default(),
+                    ),
+                    (
+                        TrainPassengerGrid,
+                        PassengerIcons::default(),
+                        Transform::from_translation(Vec3::Z * 0.1),
+                        Visibility::default(),
+                    )
+                ],
+            ))
+            .observe(ui::on_train_drag_start)
+            .observe(ui::on_inventory_drag)
+            .observe(ui::on_inventory_drag_end);
+    }
+}
+
+fn update_train_transforms(
+    mut trains_q: Query<(&TrainVisual, &mut Transform, &Children)>,
+    mut bodies_q: Query<(&mut Transform, &MeshMaterial2d<ColorMaterial>), With<TrainBody>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    metro: Res<Metro>,
+    preferences: Res<Preferences>,
+) {
+    for (visual, mut transform, children) in &mut trains_q {
+        let Some(train) = metro.train(visual.train_id) else {
+            continue;
+        };
+
+        let (position, direction) = metro.train_position(train);
+
+        transform.translation = position.extend(TRAIN_Z);
+        transform.rotation = Quat::from_rotation_z(direction.to_angle());
+
+        let mut bodies = bodies_q.iter_many_mut(children);
+        while let Some((mut body_transform, material)) = bodies.fetch_next() {
+            let length = train_length(train);
+            body_transform.scale = Vec3::new(length, TRAIN_WIDTH, 1.);
+
+            // Trains can be moved to another line, and the palette can change.
+            let color = Color::from(preferences.line_color(train.line_id));
+            if materials
+                .get(material.id())
+                .is_some_and(|material| material.color != color)
+            {
+                materials.get_mut(material.id()).unwrap().color = color;
+            }
+        }
+    }
+}
+
+/// Shows the shapes of the passengers on board in a grid on the train.
+fn update_train_passengers(
+    mut commands: Commands,
+    trains_q: Query<(&TrainVisual, &Children)>,
+    mut grids_q: Query<(Entity, &mut PassengerIcons), With<TrainPassengerGrid>>,
+    station_meshes: Res<StationMeshes>,
+    train_assets: Res<TrainAssets>,
+    metro: Res<Metro>,
+) {
+    for (visual, children) in &trains_q {
+        let Some(train) = metro.train(visual.train_id) else {
+            continue;
+        };
+
+        let kinds = train
+            .passengers
+            .iter()
+            .map(|passenger| passenger.target)
+            .collect::<Vec<_>>();
+
+        let mut grids = grids_q.iter_many_mut(children);
+        while let Some((grid, mut icons)) = grids.fetch_next() {
+            if icons.0 == kinds {
+                continue;
+            }
+
+            let columns = train.capacity().div_ceil(PASSENGER_ROWS);
+            let grid_size =
+                Vec2::new(columns as f32 - 1., PASSENGER_ROWS as f32 - 1.) * PASSENGER_ICON_SPACING;
+
+            spawn_passenger_icons(
+                &mut commands,
+                grid,
+                &kinds,
+                columns,
+                Vec2::new(-grid_size.x, grid_size.y) / 2.,
+                &station_meshes,
+                &train_assets.passenger_material,
+            );
+            icons.0 = kinds.clone();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{game::metro::LinePoint, testing::headless_app};
+
+    #[test]
+    fn removing_a_train_keeps_the_visuals_of_the_others() {
+        let mut metro = Metro::new();
+        metro.set_line_path(0, vec![0, 1]);
+        let point = LinePoint {
+            line_id: 0,
+            from: 0,
+            to: 1,
+            distance_along: 0.,
+        };
+        let first = metro.new_train_id();
+        metro.place_train(point, first);
+        let second = metro.new_train_id();
+        metro.place_train(point, second);
+
+        let mut app = headless_app();
+        app.init_asset::<Mesh>()
+            .init_asset::<ColorMaterial>()
+            .init_resource::<Preferences>()
+            .insert_resource(metro)
+            .add_systems(Startup, create_train_assets)
+            .add_systems(Update, sync_trains);
+        app.update();
+
+        let visual_of = |app: &mut App, train_id| {
+            let world = app.world_mut();
+            world
+                .query::<(Entity, &TrainVisual)>()
+                .iter(world)
+                .find(|(_, visual)| visual.train_id == train_id)
+                .map(|(entity, _)| entity)
+        };
+        let second_visual = visual_of(&mut app, second);
+
+        let mut metro = app.world_mut().resource_mut::<Metro>();
+        let first_index = metro.train_index(first).unwrap();
+        metro.remove_train(first_index, 0.);
+        app.update();
+
+        assert_eq!(visual_of(&mut app, first), None);
+        assert!(second_visual.is_some());
+        assert_eq!(visual_of(&mut app, second), second_visual);
+    }
+}
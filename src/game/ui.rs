@@ -5,25 +5,68 @@ use bevy::{
 };
 use bevy_tweening::{AnimationSystem, Animator, Lens, Tween, component_animator_system};
 
-use crate::{AppState, style};
+use crate::{
+    AppState, GameState,
+    input::{Action, ActionInput},
+    settings::Preferences,
+    style::{Theme, ThemeColor, ThemedBackground, ThemedText, place_node},
+};
 
 use super::{
     GameComponent,
-    events::*,
-    metro::{LINE_COLORS, Metro, MetroResources},
+    clock::{GameClock, GameSpeed},
+    events::{ActiveLinesChanged, HistoryRequested, InventoryChanged, NoticeRequested},
+    history::{History, NetworkEdit},
+    lines::LineDragState,
+    lines_keyboard::{self, KeyboardLine},
+    lines_visual::{HighlightedLine, SelectedLine},
+    metro::{LineId, LinePattern, Metro, MetroResources, TrainId},
+    satisfaction::Satisfaction,
+    stations,
+    statistics::Statistics,
+    trains::TrainVisual,
 };
 
 const LINE_INDICATOR_INACTIVE_SIZE: f32 = 20.;
 const LINE_INDICATOR_ACTIVE_SIZE: f32 = 50.;
+const LINE_INDICATOR_SELECTED_SIZE: f32 = 56.;
+/// How much bigger an indicator gets while the pointer is over it.
+const LINE_INDICATOR_HOVER_GROWTH: f32 = 6.;
+/// Size of one line width in the pattern drawn across active indicators.
+const LINE_INDICATOR_PATTERN_UNIT: f32 = 3.;
+const LINE_INDICATOR_PATTERN_WIDTH: f32 = 30.;
+/// How close to a line (in world units) a train has to be dropped to be put on it.
+const LINE_DROP_DISTANCE: f32 = 8.;
+/// How close to a train a carriage has to be dropped to be attached to it.
+const TRAIN_DROP_DISTANCE: f32 = 10.;
+const INVENTORY_ICON_SIZE: Vec2 = Vec2::new(36., 18.);
+const TOP_BAR_HEIGHT: f32 = 40.;
+/// How long (in real seconds) a notice stays on screen.
+const NOTICE_DURATION: f32 = 2.;
 
 pub fn plugin(app: &mut App) {
-    app.add_systems(OnEnter(AppState::Game), setup_ui)
-        .add_systems(OnEnter(AppState::Game), build_line_indicators)
+    app.init_resource::<InventoryDrag>()
+        .add_systems(OnEnter(AppState::Game), setup_ui)
+        .add_systems(OnEnter(AppState::Game), setup_line_indicators)
         .add_systems(
             Update,
-            build_line_indicators
-                .run_if(in_state(AppState::Game))
-                .run_if(on_event::<ActiveLinesChanged>),
+            (
+                update_clock,
+                update_satisfaction,
+                update_speed,
+                update_resources,
+                update_history_buttons,
+                toggle_stats_panel,
+                update_stats_panel,
+                update_inventory,
+                deselect_line.before(lines_keyboard::build_line),
+                select_line_by_number,
+                cycle_selected_line,
+                update_line_details,
+                update_line_indicators,
+                update_notice,
+            )
+                .run_if(in_state(AppState::Game)),
         )
         .add_systems(
             Update,
@@ -33,12 +76,88 @@ pub fn plugin(app: &mut App) {
         );
 }
 
-#[derive(Component, Clone)]
-struct LineIndicatorsState {
-    line_states: Vec<LineIndicatorState>,
+#[derive(Component)]
+struct ClockText;
+
+#[derive(Component)]
+struct SatisfactionText;
+
+#[derive(Component)]
+struct DeliveredText;
+
+#[derive(Component)]
+struct SpeedText;
+
+#[derive(Component)]
+struct ResourcesText;
+
+#[derive(Component)]
+struct HistoryButton(HistoryRequested);
+
+#[derive(Component)]
+struct StatsPanel;
+
+#[derive(Component)]
+struct StatsPanelText;
+
+#[derive(Component)]
+struct LineDetailsPanel;
+
+/// Shows the latest `NoticeRequested` message.
+#[derive(Component)]
+struct Notice;
+
+#[derive(Component)]
+struct NoticeText;
+
+#[derive(Component)]
+struct LineDetailsText;
+
+/// One entry of the line indicator sidebar. Spawned once, and restyled whenever the state of the
+/// line or the pointer over it changes.
+#[derive(Component)]
+struct LineIndicator {
+    line_id: LineId,
+    hovered: bool,
+    /// What the indicator currently looks like. `None` until it's first styled.
+    shown: Option<(LineIndicatorState, bool)>,
+}
+
+/// The circle inside a `LineIndicator`.
+#[derive(Component)]
+struct LineIndicatorDot;
+
+/// The line's dash pattern, drawn across its `LineIndicatorDot` while line patterns are on.
+#[derive(Component)]
+struct LineIndicatorPattern;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(super) enum InventoryItem {
+    Train,
+    Carriage,
+}
+
+#[derive(Component)]
+struct InventorySlot(InventoryItem);
+
+#[derive(Component)]
+struct InventoryText(InventoryItem);
+
+/// Follows the cursor while something is being dragged out of (or back into) the inventory.
+#[derive(Component)]
+pub(super) struct InventoryDragGhost;
+
+#[derive(Resource, Default)]
+pub(super) enum InventoryDrag {
+    #[default]
+    None,
+    Item(InventoryItem),
+    /// A train that's already running on a line. Trains can be removed while it's being dragged,
+    /// so it's kept by its id rather than its index in `Metro::trains`.
+    PlacedTrain(TrainId),
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq, Eq)]
 enum LineIndicatorState {
     Selected,
     Active,
@@ -46,7 +165,243 @@ enum LineIndicatorState {
     Unavailable,
 }
 
+impl LineIndicatorState {
+    fn size(self) -> f32 {
+        match self {
+            LineIndicatorState::Selected => LINE_INDICATOR_SELECTED_SIZE,
+            LineIndicatorState::Active => LINE_INDICATOR_ACTIVE_SIZE,
+            LineIndicatorState::Inactive | LineIndicatorState::Unavailable => {
+                LINE_INDICATOR_INACTIVE_SIZE
+            }
+        }
+    }
+
+    fn border(self, theme: &Theme) -> (f32, Srgba) {
+        match self {
+            LineIndicatorState::Selected => (7., theme.primary),
+            LineIndicatorState::Active => (5., theme.on_background),
+            LineIndicatorState::Inactive | LineIndicatorState::Unavailable => {
+                (0., theme.on_background)
+            }
+        }
+    }
+
+    fn tween(self, start: f32, end: f32) -> Tween<Node> {
+        let (ease, duration) = match self {
+            LineIndicatorState::Selected => (EaseFunction::BackOut, 300),
+            _ => (EaseFunction::BounceOut, 400),
+        };
+
+        Tween::new(
+            ease,
+            Duration::from_millis(duration),
+            NodeSizeLens { start, end },
+        )
+    }
+}
+
 fn setup_ui(mut commands: Commands) {
+    commands.spawn((
+        GameComponent,
+        StatsPanel,
+        Node {
+            display: Display::None,
+            justify_self: JustifySelf::Start,
+            align_self: AlignSelf::End,
+            margin: UiRect::all(Val::Px(10.)),
+            padding: UiRect::all(Val::Px(10.)),
+            ..default()
+        },
+        ThemedBackground::new(ThemeColor::Surface).with_alpha(0.9),
+        BorderRadius::all(Val::Px(6.)),
+        children![(
+            StatsPanelText,
+            Text::new(""),
+            TextFont {
+                font_size: 14.0,
+                ..default()
+            },
+            ThemedText(ThemeColor::OnSurface),
+        )],
+    ));
+
+    commands.spawn((
+        GameComponent,
+        Notice,
+        Node {
+            display: Display::None,
+            justify_self: JustifySelf::Center,
+            align_self: AlignSelf::Center,
+            padding: UiRect::all(Val::Px(10.)),
+            ..default()
+        },
+        ThemedBackground::new(ThemeColor::Surface).with_alpha(0.9),
+        BorderRadius::all(Val::Px(6.)),
+        Pickable::IGNORE,
+        children![(
+            NoticeText,
+            Text::new(""),
+            TextFont {
+                font_size: 14.0,
+                ..default()
+            },
+            ThemedText(ThemeColor::OnSurface),
+            Pickable::IGNORE,
+        )],
+    ));
+
+    let top_bar = commands
+        .spawn((
+            GameComponent,
+            Node {
+                width: Val::Percent(100.),
+                height: Val::Px(TOP_BAR_HEIGHT),
+                align_self: AlignSelf::Start,
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::SpaceBetween,
+                padding: UiRect::horizontal(Val::Px(16.)),
+                ..default()
+            },
+            ThemedBackground::new(ThemeColor::Surface),
+            children![
+                (ClockText, top_bar_text()),
+                (
+                    Node {
+                        column_gap: Val::Px(16.),
+                        ..default()
+                    },
+                    children![
+                        (DeliveredText, top_bar_text()),
+                        (SatisfactionText, top_bar_text())
+                    ],
+                )
+            ],
+        ))
+        .id();
+
+    let controls = commands
+        .spawn((
+            ChildOf(top_bar),
+            Node {
+                align_items: AlignItems::Center,
+                column_gap: Val::Px(16.),
+                ..default()
+            },
+        ))
+        .id();
+
+    for request in [HistoryRequested::Undo, HistoryRequested::Redo] {
+        commands
+            .spawn((
+                ChildOf(controls),
+                HistoryButton(request),
+                Button,
+                Node {
+                    padding: UiRect::axes(Val::Px(8.), Val::Px(2.)),
+                    ..default()
+                },
+                ThemedBackground::new(ThemeColor::ContainerSecondary),
+                BorderRadius::all(Val::Px(4.)),
+                children![(
+                    Text::new(format!("{request:?}")),
+                    TextFont {
+                        font_size: 14.0,
+                        ..default()
+                    },
+                    ThemedText(ThemeColor::OnSecondary),
+                    Pickable::IGNORE,
+                )],
+            ))
+            .observe(
+                |trigger: Trigger<Pointer<Click>>,
+                 buttons_q: Query<&HistoryButton>,
+                 mut history_events: EventWriter<HistoryRequested>| {
+                    if let Ok(HistoryButton(request)) = buttons_q.get(trigger.target()) {
+                        history_events.write(*request);
+                    }
+                },
+            );
+    }
+
+    commands.spawn((ChildOf(controls), SpeedText, top_bar_text()));
+    commands.spawn((ChildOf(controls), ResourcesText, top_bar_text()));
+
+    commands.spawn((
+        GameComponent,
+        LineDetailsPanel,
+        Node {
+            display: Display::None,
+            justify_self: JustifySelf::End,
+            align_self: AlignSelf::Start,
+            margin: UiRect {
+                top: Val::Px(TOP_BAR_HEIGHT + 10.),
+                ..UiRect::all(Val::Px(10.))
+            },
+            padding: UiRect::all(Val::Px(10.)),
+            ..default()
+        },
+        ThemedBackground::new(ThemeColor::Surface).with_alpha(0.9),
+        BorderRadius::all(Val::Px(6.)),
+        children![(
+            LineDetailsText,
+            Text::new(""),
+            TextFont {
+                font_size: 14.0,
+                ..default()
+            },
+            ThemedText(ThemeColor::OnSurface),
+        )],
+    ));
+
+    let inventory = commands
+        .spawn((
+            GameComponent,
+            Node {
+                justify_self: JustifySelf::Center,
+                align_self: AlignSelf::End,
+                column_gap: Val::Px(16.),
+                margin: UiRect::all(Val::Px(10.)),
+                padding: UiRect::axes(Val::Px(12.), Val::Px(6.)),
+                ..default()
+            },
+            ThemedBackground::new(ThemeColor::Surface),
+            BorderRadius::all(Val::Px(6.)),
+        ))
+        .id();
+
+    for item in [InventoryItem::Train, InventoryItem::Carriage] {
+        commands
+            .spawn((
+                InventorySlot(item),
+                ChildOf(inventory),
+                Node {
+                    align_items: AlignItems::Center,
+                    column_gap: Val::Px(6.),
+                    ..default()
+                },
+                children![
+                    (
+                        inventory_icon(item),
+                        // The whole slot is dragged, not just the icon.
+                        Pickable::IGNORE,
+                    ),
+                    (
+                        InventoryText(item),
+                        Text::new(""),
+                        TextFont {
+                            font_size: 16.0,
+                            ..default()
+                        },
+                        ThemedText(ThemeColor::OnSurface),
+                        Pickable::IGNORE,
+                    )
+                ],
+            ))
+            .observe(on_inventory_drag_start)
+            .observe(on_inventory_drag)
+            .observe(on_inventory_drag_end);
+    }
+
     // commands.spawn((
     //     Node {
     //         width: Val::Px(150.0),
@@ -92,145 +447,798 @@ fn setup_ui(mut commands: Commands) {
     // ));
 }
 
-fn build_line_indicators(
+fn inventory_icon(item: InventoryItem) -> (Node, ThemedBackground, BorderRadius) {
+    let (alpha, radius) = match item {
+        InventoryItem::Train => (1., INVENTORY_ICON_SIZE.y / 2.),
+        InventoryItem::Carriage => (0.6, 3.),
+    };
+
+    (
+        Node {
+            width: Val::Px(INVENTORY_ICON_SIZE.x),
+            height: Val::Px(INVENTORY_ICON_SIZE.y),
+            ..default()
+        },
+        ThemedBackground::new(ThemeColor::OnSurface).with_alpha(alpha),
+        BorderRadius::all(Val::Px(radius)),
+    )
+}
+
+fn update_inventory(
+    mut texts_q: Query<(&mut Text, &InventoryText)>,
+    metro_resources: Res<MetroResources>,
+    metro: Res<Metro>,
+) {
+    for (mut text, InventoryText(item)) in &mut texts_q {
+        let new_text = match item {
+            InventoryItem::Train => format!("{} trains", metro_resources.available_trains(&metro)),
+            InventoryItem::Carriage => {
+                format!("{} carriages", metro_resources.available_carriages(&metro))
+            }
+        };
+
+        if text.0 != new_text {
+            text.0 = new_text;
+        }
+    }
+}
+
+fn on_inventory_drag_start(
+    trigger: Trigger<Pointer<DragStart>>,
     mut commands: Commands,
+    slots_q: Query<&InventorySlot>,
+    mut inventory_drag: ResMut<InventoryDrag>,
     metro_resources: Res<MetroResources>,
     metro: Res<Metro>,
-    old_tree_q: Query<(Entity, &LineIndicatorsState)>,
+    mut notice_requested_events: EventWriter<NoticeRequested>,
+    ui_scale: Res<UiScale>,
 ) {
-    let old_state = if let Some((old_entity, old_state)) = old_tree_q.iter().next() {
-        commands.entity(old_entity).despawn();
-        old_state.clone()
-    } else {
-        LineIndicatorsState {
-            line_states: vec![LineIndicatorState::Unavailable; metro_resources.max_lines],
+    let Ok(InventorySlot(item)) = slots_q.get(trigger.target()) else {
+        return;
+    };
+
+    let (available, notice) = match item {
+        InventoryItem::Train => (metro_resources.available_trains(&metro), "No trains left"),
+        InventoryItem::Carriage => (
+            metro_resources.available_carriages(&metro),
+            "No carriages left",
+        ),
+    };
+    if available == 0 {
+        notice_requested_events.write(NoticeRequested(notice.to_string()));
+        return;
+    }
+
+    *inventory_drag = InventoryDrag::Item(*item);
+    spawn_drag_ghost(
+        &mut commands,
+        *item,
+        trigger.pointer_location.position,
+        &ui_scale,
+    );
+}
+
+/// Picks up a train that's running on a line, so that it can be moved to another line or put back
+/// into the inventory.
+pub(super) fn on_train_drag_start(
+    trigger: Trigger<Pointer<DragStart>>,
+    mut commands: Commands,
+    trains_q: Query<&TrainVisual>,
+    metro: Res<Metro>,
+    mut inventory_drag: ResMut<InventoryDrag>,
+    ui_scale: Res<UiScale>,
+) {
+    let Some(train) = trains_q
+        .get(trigger.target())
+        .ok()
+        .and_then(|train_visual| metro.train(train_visual.train_id))
+    else {
+        return;
+    };
+
+    *inventory_drag = InventoryDrag::PlacedTrain(train.id);
+    spawn_drag_ghost(
+        &mut commands,
+        InventoryItem::Train,
+        trigger.pointer_location.position,
+        &ui_scale,
+    );
+}
+
+fn spawn_drag_ghost(
+    commands: &mut Commands,
+    item: InventoryItem,
+    cursor_position: Vec2,
+    ui_scale: &UiScale,
+) {
+    let (icon_node, background, radius) = inventory_icon(item);
+    let mut node = Node {
+        position_type: PositionType::Absolute,
+        ..icon_node
+    };
+    place_node(
+        &mut node,
+        cursor_position,
+        -INVENTORY_ICON_SIZE / 2.,
+        ui_scale,
+    );
+
+    commands.spawn((
+        GameComponent,
+        InventoryDragGhost,
+        node,
+        background,
+        radius,
+        Pickable::IGNORE,
+    ));
+}
+
+pub(super) fn on_inventory_drag(
+    trigger: Trigger<Pointer<Drag>>,
+    mut ghost_q: Query<&mut Node, With<InventoryDragGhost>>,
+    camera_q: Single<(&Camera, &GlobalTransform)>,
+    mut highlighted_line: ResMut<HighlightedLine>,
+    inventory_drag: Res<InventoryDrag>,
+    metro: Res<Metro>,
+    ui_scale: Res<UiScale>,
+) {
+    for mut node in &mut ghost_q {
+        place_node(
+            &mut node,
+            trigger.pointer_location.position,
+            -INVENTORY_ICON_SIZE / 2.,
+            &ui_scale,
+        );
+    }
+
+    let (camera, camera_transform) = *camera_q;
+    let Ok(world_position) =
+        camera.viewport_to_world_2d(camera_transform, trigger.pointer_location.position)
+    else {
+        return;
+    };
+
+    let line_id = drop_target_line(&inventory_drag, world_position, &metro);
+    if highlighted_line.0 != line_id {
+        highlighted_line.0 = line_id;
+    }
+}
+
+/// The line the dragged item would end up on, if it was dropped at `position`.
+fn drop_target_line(drag: &InventoryDrag, position: Vec2, metro: &Metro) -> Option<LineId> {
+    match drag {
+        InventoryDrag::None => None,
+        InventoryDrag::Item(InventoryItem::Carriage) => {
+            closest_train(position, metro).map(|train_index| metro.trains[train_index].line_id)
         }
+        InventoryDrag::Item(InventoryItem::Train) | InventoryDrag::PlacedTrain(_) => metro
+            .closest_line_point(position, LINE_DROP_DISTANCE)
+            .map(|point| point.line_id),
+    }
+}
+
+fn closest_train(position: Vec2, metro: &Metro) -> Option<usize> {
+    metro
+        .trains
+        .iter()
+        .enumerate()
+        .map(|(train_index, train)| {
+            let (train_position, _) = metro.train_position(train);
+            (train_index, train_position.distance(position))
+        })
+        .filter(|(_, distance)| *distance <= TRAIN_DROP_DISTANCE)
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(train_index, _)| train_index)
+}
+
+pub(super) fn on_inventory_drag_end(
+    trigger: Trigger<Pointer<DragEnd>>,
+    mut commands: Commands,
+    ghost_q: Query<Entity, With<InventoryDragGhost>>,
+    camera_q: Single<(&Camera, &GlobalTransform)>,
+    mut inventory_drag: ResMut<InventoryDrag>,
+    mut highlighted_line: ResMut<HighlightedLine>,
+    mut inventory_changed_events: EventWriter<InventoryChanged>,
+    mut history: ResMut<History>,
+    mut metro: ResMut<Metro>,
+    metro_resources: Res<MetroResources>,
+    clock: Res<GameClock>,
+) {
+    for ghost in &ghost_q {
+        commands.entity(ghost).despawn();
+    }
+    highlighted_line.0 = None;
+
+    let drag = std::mem::take(&mut *inventory_drag);
+
+    let (camera, camera_transform) = *camera_q;
+    let Ok(position) =
+        camera.viewport_to_world_2d(camera_transform, trigger.pointer_location.position)
+    else {
+        return;
     };
 
-    let active_lines = metro.get_active_lines();
+    match drag {
+        InventoryDrag::None => {}
+        InventoryDrag::Item(InventoryItem::Train) => {
+            if metro_resources.available_trains(&metro) == 0 {
+                return;
+            }
 
-    let new_state = LineIndicatorsState {
-        line_states: (0..metro_resources.max_lines)
-            .map(|i| {
-                if i < metro_resources.available_lines {
-                    if active_lines.contains(&i) {
-                        LineIndicatorState::Active
-                    } else {
-                        LineIndicatorState::Inactive
-                    }
-                } else {
-                    LineIndicatorState::Unavailable
+            if let Some(point) = metro.closest_line_point(position, LINE_DROP_DISTANCE) {
+                let train_id = metro.new_train_id();
+                metro.place_train(point, train_id);
+                history.record(vec![NetworkEdit::PlaceTrain {
+                    train_id,
+                    point,
+                    carriages: 0,
+                }]);
+                inventory_changed_events.write(InventoryChanged);
+            }
+        }
+        InventoryDrag::Item(InventoryItem::Carriage) => {
+            if metro_resources.available_carriages(&metro) == 0 {
+                return;
+            }
+
+            if let Some(train_index) = closest_train(position, &metro) {
+                metro.trains[train_index].carriage_count += 1;
+                history.record(vec![NetworkEdit::AddCarriage {
+                    train_id: metro.trains[train_index].id,
+                }]);
+                inventory_changed_events.write(InventoryChanged);
+            }
+        }
+        InventoryDrag::PlacedTrain(train_id) => {
+            let Some(train_index) = metro.train_index(train_id) else {
+                return;
+            };
+
+            // Dropping the train anywhere but on a line puts it back into the inventory, along
+            // with its carriages.
+            let point = metro.closest_line_point(position, LINE_DROP_DISTANCE);
+            let previous_point = metro.train_point(&metro.trains[train_index]);
+            let train = metro.remove_train(train_index, clock.minutes);
+
+            let mut edits = vec![NetworkEdit::RemoveTrain {
+                train_id: train.id,
+                point: previous_point,
+                carriages: train.carriage_count,
+            }];
+
+            if let Some(point) = point {
+                metro.place_train(point, train.id);
+                if let Some(placed) = metro.trains.last_mut() {
+                    placed.carriage_count = train.carriage_count;
                 }
-            })
-            .collect(),
+                edits.push(NetworkEdit::PlaceTrain {
+                    train_id: train.id,
+                    point,
+                    carriages: train.carriage_count,
+                });
+            }
+
+            history.record(edits);
+            inventory_changed_events.write(InventoryChanged);
+        }
+    }
+}
+
+fn on_line_indicator_click(
+    trigger: Trigger<Pointer<Click>>,
+    indicators_q: Query<&LineIndicator>,
+    mut selected_line: ResMut<SelectedLine>,
+    metro: Res<Metro>,
+) {
+    let Ok(indicator) = indicators_q.get(trigger.target()) else {
+        return;
+    };
+
+    // Lines without stations have nothing to show.
+    if !metro.lines.contains_key(&indicator.line_id) {
+        return;
+    }
+
+    selected_line.0 = if selected_line.0 == Some(indicator.line_id) {
+        None
+    } else {
+        Some(indicator.line_id)
+    };
+}
+
+/// Only deselects when there's no line being drawn or built, otherwise cancelling that comes first.
+fn deselect_line(
+    input: ActionInput,
+    drag_state: Res<LineDragState>,
+    keyboard_line: Res<KeyboardLine>,
+    mut selected_line: ResMut<SelectedLine>,
+) {
+    if input.just_pressed(Action::Cancel)
+        && selected_line.0.is_some()
+        && !drag_state.is_active()
+        && !keyboard_line.is_building()
+    {
+        selected_line.0 = None;
+    }
+}
+
+fn select_line_by_number(
+    input: ActionInput,
+    metro: Res<Metro>,
+    mut selected_line: ResMut<SelectedLine>,
+) {
+    for (line_id, action) in Action::SELECT_LINES.into_iter().enumerate() {
+        if input.just_pressed(action) && metro.lines.contains_key(&line_id) {
+            selected_line.0 = Some(line_id);
+        }
+    }
+}
+
+/// Steps through the lines on the map, as if their indicators were clicked one after another.
+fn cycle_selected_line(
+    input: ActionInput,
+    metro: Res<Metro>,
+    mut selected_line: ResMut<SelectedLine>,
+) {
+    let step = input.just_pressed(Action::NextLine) as i32
+        - input.just_pressed(Action::PreviousLine) as i32;
+    if step == 0 {
+        return;
+    }
+
+    let mut line_ids = metro.lines.keys().copied().collect::<Vec<_>>();
+    if line_ids.is_empty() {
+        return;
+    }
+    line_ids.sort();
+
+    let count = line_ids.len() as i32;
+    let index = match selected_line
+        .0
+        .and_then(|line_id| line_ids.iter().position(|id| *id == line_id))
+    {
+        Some(index) => index as i32 + step,
+        None if step > 0 => step - 1,
+        None => count + step,
     };
 
-    commands
+    selected_line.0 = Some(line_ids[index.rem_euclid(count) as usize]);
+}
+
+fn update_line_details(
+    mut panel: Single<&mut Node, With<LineDetailsPanel>>,
+    mut details_text: Single<&mut Text, With<LineDetailsText>>,
+    selected_line: Res<SelectedLine>,
+    metro: Res<Metro>,
+    statistics: Res<Statistics>,
+) {
+    let Some((line_id, path)) = selected_line
+        .0
+        .and_then(|line_id| Some((line_id, metro.lines.get(&line_id)?)))
+    else {
+        if panel.display != Display::None {
+            panel.display = Display::None;
+        }
+        return;
+    };
+
+    if panel.display == Display::None {
+        panel.display = Display::Flex;
+    }
+
+    let stations = path
+        .iter()
+        .map(|station_id| stations::kind_name(metro.stations[*station_id].kind))
+        .collect::<Vec<_>>();
+
+    let trains = metro
+        .trains
+        .iter()
+        .filter(|train| train.line_id == line_id)
+        .collect::<Vec<_>>();
+    let carriages = trains
+        .iter()
+        .map(|train| train.carriage_count)
+        .sum::<usize>();
+    let on_board = trains
+        .iter()
+        .map(|train| train.passengers.len())
+        .sum::<usize>();
+    let capacity = trains.iter().map(|train| train.capacity()).sum::<usize>();
+
+    let mut lines = vec![
+        format!("Line {} (Esc to deselect)", line_id + 1),
+        format!("{} stations: {}", stations.len(), stations.join(" - ")),
+        format!("{} trains, {} carriages", trains.len(), carriages),
+        format!("Load: {on_board}/{capacity} on board"),
+    ];
+
+    match statistics.lines.get(&line_id) {
+        Some(ridership) => lines.push(format!(
+            "Ridership: {} riders, wait {:.0} min (median)",
+            ridership.completed + ridership.abandoned,
+            ridership.wait_time().p50
+        )),
+        None => lines.push("Ridership: no riders yet".to_string()),
+    }
+
+    let text = lines.join("\n");
+    if details_text.0 != text {
+        details_text.0 = text;
+    }
+}
+
+fn top_bar_text() -> impl Bundle {
+    (
+        Text::new(""),
+        TextFont {
+            font_size: 18.0,
+            ..default()
+        },
+        ThemedText(ThemeColor::OnSurface),
+    )
+}
+
+fn update_clock(mut clock_text: Single<&mut Text, With<ClockText>>, clock: Res<GameClock>) {
+    let text = format!(
+        "{} {}  Day {}  Week {}",
+        clock.weekday_name(),
+        clock.time_of_day(),
+        clock.day() + 1,
+        clock.week() + 1
+    );
+
+    if clock_text.0 != text {
+        clock_text.0 = text;
+    }
+}
+
+fn update_satisfaction(
+    mut satisfaction_text: Single<&mut Text, (With<SatisfactionText>, Without<DeliveredText>)>,
+    mut delivered_text: Single<&mut Text, With<DeliveredText>>,
+    satisfaction: Res<Satisfaction>,
+) {
+    if !satisfaction.is_changed() && !satisfaction_text.0.is_empty() {
+        return;
+    }
+
+    delivered_text.0 = format!("{} delivered", satisfaction.delivered);
+    satisfaction_text.0 = format!(
+        "Satisfaction {:.0}% ({} gave up)",
+        satisfaction.score() * 100.,
+        satisfaction.abandoned
+    );
+}
+
+fn update_history_buttons(
+    mut buttons_q: Query<(&HistoryButton, &mut ThemedBackground)>,
+    history: Res<History>,
+) {
+    if !history.is_changed() {
+        return;
+    }
+
+    for (HistoryButton(request), mut background) in &mut buttons_q {
+        let available = match request {
+            HistoryRequested::Undo => history.can_undo(),
+            HistoryRequested::Redo => history.can_redo(),
+        };
+
+        background.alpha = if available { 1. } else { 0.4 };
+    }
+}
+
+fn update_speed(
+    mut speed_text: Single<&mut Text, With<SpeedText>>,
+    game_speed: Res<GameSpeed>,
+    game_state: Res<State<GameState>>,
+) {
+    let text = match game_state.get() {
+        GameState::Paused => "Paused".to_string(),
+        GameState::Running => format!("Speed x{}", game_speed.0),
+    };
+
+    if speed_text.0 != text {
+        speed_text.0 = text;
+    }
+}
+
+fn update_resources(
+    mut resources_text: Single<&mut Text, With<ResourcesText>>,
+    mut active_lines_changed_events: EventReader<ActiveLinesChanged>,
+    mut inventory_changed_events: EventReader<InventoryChanged>,
+    metro_resources: Res<MetroResources>,
+    metro: Res<Metro>,
+) {
+    let changed = active_lines_changed_events.read().count() > 0
+        || inventory_changed_events.read().count() > 0
+        || metro_resources.is_changed();
+    if !changed && !resources_text.0.is_empty() {
+        return;
+    }
+
+    let free_lines = metro_resources
+        .available_lines
+        .saturating_sub(metro.lines.len());
+
+    resources_text.0 = format!(
+        "Lines {}  Trains {}  Carriages {}  Tunnels {}",
+        free_lines,
+        metro_resources.available_trains(&metro),
+        metro_resources.available_carriages(&metro),
+        metro_resources.tunnels
+    );
+}
+
+fn toggle_stats_panel(mut stats_panel: Single<&mut Node, With<StatsPanel>>, input: ActionInput) {
+    if !input.just_pressed(Action::ToggleStats) {
+        return;
+    }
+
+    stats_panel.display = match stats_panel.display {
+        Display::None => Display::Flex,
+        _ => Display::None,
+    };
+}
+
+fn update_stats_panel(
+    mut stats_text: Single<&mut Text, With<StatsPanelText>>,
+    statistics: Res<Statistics>,
+) {
+    if !statistics.is_changed() {
+        return;
+    }
+
+    let total = &statistics.total;
+    let wait_time = total.wait_time();
+    let mut lines = vec![
+        "Statistics (Tab to hide)".to_string(),
+        format!(
+            "{} delivered, {} gave up. wait {:.0}/{:.0} min (median/p90), ride {:.0} min",
+            total.completed,
+            total.abandoned,
+            wait_time.p50,
+            wait_time.p90,
+            total.travel_time().mean,
+        ),
+        String::new(),
+    ];
+
+    let mut line_ids = statistics.lines.keys().copied().collect::<Vec<_>>();
+    line_ids.sort();
+    for line_id in line_ids {
+        let line = &statistics.lines[&line_id];
+        let wait_time = line.wait_time();
+        lines.push(format!(
+            "Line {}: {} riders, wait {:.0}/{:.0} min, ride {:.0} min",
+            line_id + 1,
+            line.completed + line.abandoned,
+            wait_time.p50,
+            wait_time.p90,
+            line.travel_time().mean,
+        ));
+    }
+
+    lines.push(String::new());
+    for (week, aggregate) in statistics.weeks.iter().enumerate() {
+        lines.push(format!(
+            "Week {}: {} delivered, {} gave up",
+            week + 1,
+            aggregate.completed,
+            aggregate.abandoned
+        ));
+    }
+
+    stats_text.0 = lines.join("\n");
+}
+
+fn setup_line_indicators(mut commands: Commands, metro_resources: Res<MetroResources>) {
+    let sidebar = commands
         .spawn((
             GameComponent,
-            new_state.clone(),
             Node {
                 justify_self: JustifySelf::End,
                 align_self: AlignSelf::Center,
                 flex_direction: FlexDirection::Column,
                 width: Val::Px(100.),
-                // width: Val::Auto,
                 height: Val::Auto,
                 ..default()
             },
         ))
-        .with_children(|parent| {
-            for (i, (new_state, old_state)) in
-                std::iter::zip(new_state.line_states, old_state.line_states).enumerate()
-            {
-                parent
-                    .spawn((Node {
-                        justify_self: JustifySelf::Center,
-                        align_self: AlignSelf::Center,
-                        align_content: AlignContent::Center,
+        .id();
+
+    for line_id in 0..metro_resources.max_lines {
+        commands
+            .spawn((
+                ChildOf(sidebar),
+                LineIndicator {
+                    line_id,
+                    hovered: false,
+                    shown: None,
+                },
+                Node {
+                    justify_self: JustifySelf::Center,
+                    align_self: AlignSelf::Center,
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    width: Val::Px(LINE_INDICATOR_SELECTED_SIZE + 10.),
+                    height: Val::Px(LINE_INDICATOR_SELECTED_SIZE + 10.),
+                    ..default()
+                },
+                children![(
+                    LineIndicatorDot,
+                    Node {
+                        width: Val::Px(LINE_INDICATOR_INACTIVE_SIZE),
+                        height: Val::Px(LINE_INDICATOR_INACTIVE_SIZE),
+                        align_items: AlignItems::Center,
                         justify_content: JustifyContent::Center,
-                        width: Val::Auto,
-                        height: Val::Px(LINE_INDICATOR_ACTIVE_SIZE + 10.),
                         ..default()
-                    },))
+                    },
+                    // Styled by `update_line_indicators`.
+                    BackgroundColor::default(),
+                    BorderColor::default(),
+                    BorderRadius::MAX,
+                    Pickable::IGNORE,
+                    children![(
+                        LineIndicatorPattern,
+                        Node {
+                            width: Val::Px(LINE_INDICATOR_PATTERN_WIDTH),
+                            align_items: AlignItems::Center,
+                            flex_shrink: 0.,
+                            overflow: Overflow::clip(),
+                            ..default()
+                        },
+                        Pickable::IGNORE,
+                    )],
+                )],
+            ))
+            .observe(on_line_indicator_click)
+            .observe(
+                |trigger: Trigger<Pointer<Over>>, mut indicators_q: Query<&mut LineIndicator>| {
+                    if let Ok(mut indicator) = indicators_q.get_mut(trigger.target()) {
+                        indicator.hovered = true;
+                    }
+                },
+            )
+            .observe(
+                |trigger: Trigger<Pointer<Out>>, mut indicators_q: Query<&mut LineIndicator>| {
+                    if let Ok(mut indicator) = indicators_q.get_mut(trigger.target()) {
+                        indicator.hovered = false;
+                    }
+                },
+            );
+    }
+}
+
+type LineIndicatorDotData<'a> = (
+    Entity,
+    &'a mut Node,
+    &'a mut BackgroundColor,
+    &'a mut BorderColor,
+    &'a Children,
+);
+
+/// Restyles the indicators whose line or hover state changed, animating them from whatever size
+/// they're at to the new one.
+fn update_line_indicators(
+    mut commands: Commands,
+    mut indicators_q: Query<(&mut LineIndicator, &Children)>,
+    mut dots_q: Query<LineIndicatorDotData, With<LineIndicatorDot>>,
+    patterns_q: Query<Entity, With<LineIndicatorPattern>>,
+    metro_resources: Res<MetroResources>,
+    metro: Res<Metro>,
+    selected_line: Res<SelectedLine>,
+    preferences: Res<Preferences>,
+    theme: Res<Theme>,
+) {
+    let active_lines = metro.get_active_lines();
+
+    for (mut indicator, children) in &mut indicators_q {
+        let line_id = indicator.line_id;
+        let state = if line_id >= metro_resources.available_lines {
+            LineIndicatorState::Unavailable
+        } else if selected_line.0 == Some(line_id) && metro.lines.contains_key(&line_id) {
+            LineIndicatorState::Selected
+        } else if active_lines.contains(&line_id) {
+            LineIndicatorState::Active
+        } else {
+            LineIndicatorState::Inactive
+        };
+        // Only lines that can be drawn react to the pointer.
+        let hovered = indicator.hovered && state != LineIndicatorState::Unavailable;
+
+        // The palette, the patterns and the theme can change while the game is running.
+        if indicator.shown == Some((state, hovered))
+            && !preferences.is_changed()
+            && !theme.is_changed()
+        {
+            continue;
+        }
+        let is_first_update = indicator.shown.is_none();
+        indicator.shown = Some((state, hovered));
+
+        let size = state.size()
+            + if hovered {
+                LINE_INDICATOR_HOVER_GROWTH
+            } else {
+                0.
+            };
+        let (border_width, border_color) = state.border(&theme);
+        let background = match state {
+            LineIndicatorState::Unavailable => theme.on_background,
+            _ => preferences.line_color(line_id),
+        };
+        // Too small to draw the pattern in otherwise.
+        let pattern = match state {
+            LineIndicatorState::Selected | LineIndicatorState::Active => {
+                preferences.line_pattern(line_id)
+            }
+            _ => LinePattern::Solid,
+        };
+
+        let mut dots = dots_q.iter_many_mut(children);
+        while let Some((dot, mut node, mut background_color, mut dot_border_color, dot_children)) =
+            dots.fetch_next()
+        {
+            for pattern_entity in patterns_q.iter_many(dot_children) {
+                commands
+                    .entity(pattern_entity)
+                    .despawn_related::<Children>()
                     .with_children(|parent| {
-                        match new_state {
-                            LineIndicatorState::Selected => todo!(),
-
-                            LineIndicatorState::Active => {
-                                let tween = Tween::new(
-                                    EaseFunction::BounceOut,
-                                    Duration::from_millis(400),
-                                    NodeSizeLens {
-                                        start: match old_state {
-                                            LineIndicatorState::Active => {
-                                                LINE_INDICATOR_ACTIVE_SIZE
-                                            }
-                                            _ => LINE_INDICATOR_INACTIVE_SIZE,
-                                        },
-                                        end: LINE_INDICATOR_ACTIVE_SIZE,
-                                    },
-                                );
-
-                                parent.spawn((
-                                    Node {
-                                        justify_self: JustifySelf::Center,
-                                        align_self: AlignSelf::Center,
-                                        width: Val::Px(LINE_INDICATOR_ACTIVE_SIZE),
-                                        height: Val::Px(LINE_INDICATOR_ACTIVE_SIZE),
-                                        border: UiRect::all(Val::Px(5.)),
-                                        ..default()
-                                    },
-                                    Animator::new(tween),
-                                    BackgroundColor(LINE_COLORS[i].into()),
-                                    BorderColor(style::ON_BACKGROUND.into()),
-                                    BorderRadius::all(Val::Px(LINE_INDICATOR_ACTIVE_SIZE / 2.)),
-                                ))
-                            }
-                            LineIndicatorState::Inactive => {
-                                let tween = Tween::new(
-                                    EaseFunction::BounceOut,
-                                    Duration::from_millis(400),
-                                    NodeSizeLens {
-                                        start: match old_state {
-                                            LineIndicatorState::Active => {
-                                                LINE_INDICATOR_ACTIVE_SIZE
-                                            }
-                                            _ => LINE_INDICATOR_INACTIVE_SIZE,
-                                        },
-                                        end: LINE_INDICATOR_INACTIVE_SIZE,
-                                    },
-                                );
-                                parent.spawn((
-                                    Node {
-                                        justify_self: JustifySelf::Center,
-                                        align_self: AlignSelf::Center,
-                                        width: Val::Px(LINE_INDICATOR_INACTIVE_SIZE),
-                                        height: Val::Px(LINE_INDICATOR_INACTIVE_SIZE),
-                                        // border: UiRect::all(Val::Px(3.)),
-                                        ..default()
-                                    },
-                                    Animator::new(tween),
-                                    BackgroundColor(LINE_COLORS[i].into()),
-                                    // BorderColor(style::ON_BACKGROUND.into()),
-                                    BorderRadius::all(Val::Px(LINE_INDICATOR_ACTIVE_SIZE) / 2.),
-                                ))
-                            }
-                            LineIndicatorState::Unavailable => parent.spawn((
-                                Node {
-                                    justify_self: JustifySelf::Center,
-                                    align_self: AlignSelf::Center,
-                                    width: Val::Px(LINE_INDICATOR_INACTIVE_SIZE),
-                                    height: Val::Px(LINE_INDICATOR_INACTIVE_SIZE),
-                                    ..default()
-                                },
-                                BackgroundColor(style::ON_BACKGROUND.into()),
-                                BorderRadius::all(Val::Px(LINE_INDICATOR_INACTIVE_SIZE) / 2.),
-                            )),
-                        };
+                        spawn_pattern_segments(parent, pattern, theme.background)
                     });
             }
-        });
+
+            node.border = UiRect::all(Val::Px(border_width));
+            background_color.0 = background.into();
+            dot_border_color.0 = border_color.into();
+
+            let current_size = match node.width {
+                Val::Px(width) => width,
+                _ => size,
+            };
+            if is_first_update || current_size == size || preferences.reduced_motion {
+                node.width = Val::Px(size);
+                node.height = Val::Px(size);
+                commands.entity(dot).remove::<Animator<Node>>();
+            } else {
+                commands
+                    .entity(dot)
+                    .insert(Animator::new(state.tween(current_size, size)));
+            }
+        }
+    }
+}
+
+/// Two repeats of the pattern, as UI nodes. Nothing for a solid line.
+fn spawn_pattern_segments(parent: &mut ChildSpawnerCommands, pattern: LinePattern, color: Srgba) {
+    let dashes = pattern.dashes();
+
+    for (i, length) in dashes.iter().cycle().take(dashes.len() * 2).enumerate() {
+        // Drawn parts get round ends, like on the map.
+        if i % 2 == 0 {
+            parent.spawn((
+                Node {
+                    width: Val::Px((length + 1.) * LINE_INDICATOR_PATTERN_UNIT),
+                    height: Val::Px(LINE_INDICATOR_PATTERN_UNIT),
+                    flex_shrink: 0.,
+                    ..default()
+                },
+                BackgroundColor(color.into()),
+                BorderRadius::MAX,
+                Pickable::IGNORE,
+            ));
+        } else {
+            parent.spawn((
+                Node {
+                    width: Val::Px((length - 1.) * LINE_INDICATOR_PATTERN_UNIT),
+                    flex_shrink: 0.,
+                    ..default()
+                },
+                Pickable::IGNORE,
+            ));
+        }
+    }
 }
 
 struct NodeSizeLens {
@@ -244,3 +1252,86 @@ impl Lens<Node> for NodeSizeLens {
         target.height = Val::Px(FloatExt::lerp(self.start, self.end, ratio));
     }
 }
+
+fn update_notice(
+    mut notice_requested_events: EventReader<NoticeRequested>,
+    mut notice: Single<&mut Node, With<Notice>>,
+    mut notice_text: Single<&mut Text, With<NoticeText>>,
+    mut hide_at: Local<f32>,
+    time: Res<Time<Real>>,
+) {
+    if let Some(NoticeRequested(message)) = notice_requested_events.read().last() {
+        notice_text.0.clone_from(message);
+        notice.display = Display::Flex;
+        *hide_at = time.elapsed_secs() + NOTICE_DURATION;
+    } else if notice.display != Display::None && time.elapsed_secs() >= *hide_at {
+        notice.display = Display::None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{FRAME_TIME, headless_app, spawn_gamepad, tap_gamepad_button};
+
+    #[test]
+    fn shoulder_buttons_cycle_the_selected_line() {
+        let mut metro = Metro::new();
+        metro.set_line_path(0, vec![0, 1]);
+        metro.set_line_path(2, vec![1, 2]);
+
+        let mut app = headless_app();
+        app.insert_resource(metro)
+            .init_resource::<SelectedLine>()
+            .add_systems(Update, cycle_selected_line);
+        let gamepad = spawn_gamepad(&mut app);
+        app.update();
+
+        let mut selected_lines = Vec::new();
+        for button in [
+            GamepadButton::RightTrigger,
+            GamepadButton::RightTrigger,
+            GamepadButton::RightTrigger,
+            GamepadButton::LeftTrigger,
+        ] {
+            tap_gamepad_button(&mut app, gamepad, button);
+            selected_lines.push(app.world().resource::<SelectedLine>().0);
+        }
+
+        assert_eq!(selected_lines, [Some(0), Some(2), Some(0), Some(2)]);
+    }
+    #[test]
+    fn notices_hide_after_a_while() {
+        let mut app = headless_app();
+        app.add_event::<NoticeRequested>()
+            .add_systems(Update, update_notice);
+        let notice = app
+            .world_mut()
+            .spawn((
+                Notice,
+                Node {
+                    display: Display::None,
+                    ..default()
+                },
+            ))
+            .id();
+        app.world_mut().spawn((NoticeText, Text::new("")));
+        app.update();
+
+        app.world_mut()
+            .send_event(NoticeRequested("No lines available".to_string()));
+        app.update();
+        assert_eq!(
+            app.world().get::<Node>(notice).unwrap().display,
+            Display::Flex
+        );
+
+        for _ in 0..(NOTICE_DURATION / FRAME_TIME.as_secs_f32()) as usize + 1 {
+            app.update();
+        }
+        assert_eq!(
+            app.world().get::<Node>(notice).unwrap().display,
+            Display::None
+        );
+    }
+}
@@ -0,0 +1,372 @@
+use std::collections::BTreeMap;
+
+use bevy::{ecs::system::SystemParam, prelude::*};
+use serde::{Deserialize, Serialize};
+
+/// Everything the player can do with a key or a button. Dragging and clicking with the mouse is
+/// handled by picking, so it isn't here.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum Action {
+    Pause,
+    NormalSpeed,
+    FastSpeed,
+    FastestSpeed,
+    Undo,
+    Redo,
+    /// Cancels the line being drawn, or deselects the selected line if there isn't one.
+    Cancel,
+    NextLine,
+    PreviousLine,
+    SelectLine1,
+    SelectLine2,
+    SelectLine3,
+    SelectLine4,
+    SelectLine5,
+    SelectLine6,
+    SelectLine7,
+    SelectLine8,
+    SelectLine9,
+    ToggleStats,
+    PanUp,
+    PanLeft,
+    PanDown,
+    PanRight,
+    /// Pans while held, following the mouse.
+    DragView,
+    ZoomIn,
+    ZoomOut,
+    FitNetwork,
+    FocusUp,
+    FocusLeft,
+    FocusDown,
+    FocusRight,
+    /// Starts a line at the focused station, or adds the focused station to the line being built.
+    AddStation,
+    RemoveStation,
+    CommitLine,
+    /// Clicks with the gamepad cursor.
+    Click,
+    Export,
+}
+
+impl Action {
+    /// In the order they're listed on the controls screen.
+    pub const ALL: [Action; 36] = [
+        Action::Pause,
+        Action::NormalSpeed,
+        Action::FastSpeed,
+        Action::FastestSpeed,
+        Action::Undo,
+        Action::Redo,
+        Action::Cancel,
+        Action::NextLine,
+        Action::PreviousLine,
+        Action::SelectLine1,
+        Action::SelectLine2,
+        Action::SelectLine3,
+        Action::SelectLine4,
+        Action::SelectLine5,
+        Action::SelectLine6,
+        Action::SelectLine7,
+        Action::SelectLine8,
+        Action::SelectLine9,
+        Action::ToggleStats,
+        Action::PanUp,
+        Action::PanLeft,
+        Action::PanDown,
+        Action::PanRight,
+        Action::DragView,
+        Action::ZoomIn,
+        Action::ZoomOut,
+        Action::FitNetwork,
+        Action::FocusUp,
+        Action::FocusLeft,
+        Action::FocusDown,
+        Action::FocusRight,
+        Action::AddStation,
+        Action::RemoveStation,
+        Action::CommitLine,
+        Action::Click,
+        Action::Export,
+    ];
+
+    pub const GAME_SPEEDS: [Action; 3] =
+        [Action::NormalSpeed, Action::FastSpeed, Action::FastestSpeed];
+
+    /// `SELECT_LINES[line_id]` selects the line.
+    pub const SELECT_LINES: [Action; 9] = [
+        Action::SelectLine1,
+        Action::SelectLine2,
+        Action::SelectLine3,
+        Action::SelectLine4,
+        Action::SelectLine5,
+        Action::SelectLine6,
+        Action::SelectLine7,
+        Action::SelectLine8,
+        Action::SelectLine9,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Action::Pause => "Pause",
+            Action::NormalSpeed => "Normal speed",
+            Action::FastSpeed => "Fast speed",
+            Action::FastestSpeed => "Fastest speed",
+            Action::Undo => "Undo",
+            Action::Redo => "Redo",
+            Action::Cancel => "Cancel",
+            Action::NextLine => "Next line",
+            Action::PreviousLine => "Previous line",
+            Action::SelectLine1 => "Select line 1",
+            Action::SelectLine2 => "Select line 2",
+            Action::SelectLine3 => "Select line 3",
+            Action::SelectLine4 => "Select line 4",
+            Action::SelectLine5 => "Select line 5",
+            Action::SelectLine6 => "Select line 6",
+            Action::SelectLine7 => "Select line 7",
+            Action::SelectLine8 => "Select line 8",
+            Action::SelectLine9 => "Select line 9",
+            Action::ToggleStats => "Statistics",
+            Action::PanUp => "Pan up",
+            Action::PanLeft => "Pan left",
+            Action::PanDown => "Pan down",
+            Action::PanRight => "Pan right",
+            Action::DragView => "Drag the view",
+            Action::ZoomIn => "Zoom in",
+            Action::ZoomOut => "Zoom out",
+            Action::FitNetwork => "Fit the network",
+            Action::FocusUp => "Focus up",
+            Action::FocusLeft => "Focus left",
+            Action::FocusDown => "Focus down",
+            Action::FocusRight => "Focus right",
+            Action::AddStation => "Add station",
+            Action::RemoveStation => "Remove station",
+            Action::CommitLine => "Finish line",
+            Action::Click => "Click",
+            Action::Export => "Export statistics",
+        }
+    }
+}
+
+/// A key is only triggered with exactly the modifiers it's bound with, so that e.g. undo doesn't
+/// fire together with redo.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Binding {
+    Key(KeyCode),
+    ControlKey(KeyCode),
+    ControlShiftKey(KeyCode),
+    Mouse(MouseButton),
+    Gamepad(GamepadButton),
+}
+
+impl Binding {
+    pub fn label(&self) -> String {
+        match self {
+            Binding::Key(key) => key_label(*key),
+            Binding::ControlKey(key) => format!("Ctrl+{}", key_label(*key)),
+            Binding::ControlShiftKey(key) => format!("Ctrl+Shift+{}", key_label(*key)),
+            Binding::Mouse(button) => format!("{button:?} mouse button"),
+            Binding::Gamepad(button) => format!("Gamepad {button:?}"),
+        }
+    }
+
+    /// Whether both are on the same device, so that rebinding one replaces the other.
+    pub fn same_device(&self, other: &Binding) -> bool {
+        matches!(
+            (self, other),
+            (
+                Binding::Key(_) | Binding::ControlKey(_) | Binding::ControlShiftKey(_),
+                Binding::Key(_) | Binding::ControlKey(_) | Binding::ControlShiftKey(_),
+            ) | (Binding::Mouse(_), Binding::Mouse(_))
+                | (Binding::Gamepad(_), Binding::Gamepad(_))
+        )
+    }
+}
+
+fn key_label(key: KeyCode) -> String {
+    let name = format!("{key:?}");
+    name.strip_prefix("Key")
+        .or_else(|| name.strip_prefix("Digit"))
+        .unwrap_or(&name)
+        .to_string()
+}
+
+/// What triggers every action. Saved in the settings file.
+#[derive(Resource, Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct Bindings(pub BTreeMap<Action, Vec<Binding>>);
+
+impl Default for Bindings {
+    fn default() -> Self {
+        use Binding::*;
+
+        let mut bindings = BTreeMap::from([
+            (
+                Action::Pause,
+                vec![Key(KeyCode::Space), Gamepad(GamepadButton::Start)],
+            ),
+            (Action::NormalSpeed, vec![Key(KeyCode::Digit1)]),
+            (Action::FastSpeed, vec![Key(KeyCode::Digit2)]),
+            (Action::FastestSpeed, vec![Key(KeyCode::Digit3)]),
+            (Action::Undo, vec![ControlKey(KeyCode::KeyZ)]),
+            (Action::Redo, vec![ControlShiftKey(KeyCode::KeyZ)]),
+            (
+                Action::Cancel,
+                vec![
+                    Key(KeyCode::Escape),
+                    Mouse(MouseButton::Right),
+                    Gamepad(GamepadButton::East),
+                ],
+            ),
+            (Action::NextLine, vec![Gamepad(GamepadButton::RightTrigger)]),
+            (
+                Action::PreviousLine,
+                vec![Gamepad(GamepadButton::LeftTrigger)],
+            ),
+            (
+                Action::ToggleStats,
+                vec![Key(KeyCode::Tab), Gamepad(GamepadButton::Select)],
+            ),
+            (Action::PanUp, vec![Key(KeyCode::KeyW)]),
+            (Action::PanLeft, vec![Key(KeyCode::KeyA)]),
+            (Action::PanDown, vec![Key(KeyCode::KeyS)]),
+            (Action::PanRight, vec![Key(KeyCode::KeyD)]),
+            (Action::DragView, vec![Mouse(MouseButton::Middle)]),
+            (
+                Action::ZoomIn,
+                vec![Key(KeyCode::Equal), Gamepad(GamepadButton::RightTrigger2)],
+            ),
+            (
+                Action::ZoomOut,
+                vec![Key(KeyCode::Minus), Gamepad(GamepadButton::LeftTrigger2)],
+            ),
+            (
+                Action::FitNetwork,
+                vec![Key(KeyCode::KeyF), Gamepad(GamepadButton::North)],
+            ),
+            (Action::FocusUp, vec![Key(KeyCode::ArrowUp)]),
+            (Action::FocusLeft, vec![Key(KeyCode::ArrowLeft)]),
+            (Action::FocusDown, vec![Key(KeyCode::ArrowDown)]),
+            (Action::FocusRight, vec![Key(KeyCode::ArrowRight)]),
+            (Action::AddStation, vec![Key(KeyCode::KeyL)]),
+            (Action::RemoveStation, vec![Key(KeyCode::Backspace)]),
+            (Action::CommitLine, vec![Key(KeyCode::Enter)]),
+            (Action::Click, vec![Gamepad(GamepadButton::South)]),
+            (Action::Export, vec![Key(KeyCode::F5)]),
+        ]);
+
+        // Ctrl+1 to Ctrl+9, the number keys alone change the speed.
+        const DIGITS: [KeyCode; 9] = [
+            KeyCode::Digit1,
+            KeyCode::Digit2,
+            KeyCode::Digit3,
+            KeyCode::Digit4,
+            KeyCode::Digit5,
+            KeyCode::Digit6,
+            KeyCode::Digit7,
+            KeyCode::Digit8,
+            KeyCode::Digit9,
+        ];
+        for (action, key) in Action::SELECT_LINES.into_iter().zip(DIGITS) {
+            bindings.insert(action, vec![ControlKey(key)]);
+        }
+
+        Bindings(bindings)
+    }
+}
+
+impl Bindings {
+    pub fn get(&self, action: Action) -> &[Binding] {
+        self.0.get(&action).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    /// Binds `binding` to `action` instead of whatever was bound to it on the same device, and
+    /// takes it away from any other action.
+    pub fn rebind(&mut self, action: Action, binding: Binding) {
+        for bindings in self.0.values_mut() {
+            bindings.retain(|other| *other != binding);
+        }
+
+        let bindings = self.0.entry(action).or_default();
+        bindings.retain(|other| !other.same_device(&binding));
+        bindings.push(binding);
+    }
+
+    /// Leaves `action` without any bindings. It stays that way when the settings are loaded again.
+    pub fn clear(&mut self, action: Action) {
+        self.0.insert(action, Vec::new());
+    }
+
+    /// Actions added since the settings file was written get their default bindings.
+    pub fn fill_missing(&mut self) {
+        for (action, bindings) in Bindings::default().0 {
+            self.0.entry(action).or_insert(bindings);
+        }
+    }
+}
+
+/// Reads actions instead of raw input, so that the bindings can be changed.
+#[derive(SystemParam)]
+pub struct ActionInput<'w, 's> {
+    bindings: Res<'w, Bindings>,
+    keys: Res<'w, ButtonInput<KeyCode>>,
+    mouse_buttons: Res<'w, ButtonInput<MouseButton>>,
+    gamepads: Query<'w, 's, &'static Gamepad>,
+}
+
+impl ActionInput<'_, '_> {
+    pub fn just_pressed(&self, action: Action) -> bool {
+        self.any_binding(
+            action,
+            |key, keys| keys.just_pressed(key),
+            |button, buttons| buttons.just_pressed(button),
+            |button, gamepad| gamepad.just_pressed(button),
+        )
+    }
+
+    pub fn pressed(&self, action: Action) -> bool {
+        self.any_binding(
+            action,
+            |key, keys| keys.pressed(key),
+            |button, buttons| buttons.pressed(button),
+            |button, gamepad| gamepad.pressed(button),
+        )
+    }
+
+    pub fn just_released(&self, action: Action) -> bool {
+        self.any_binding(
+            action,
+            |key, keys| keys.just_released(key),
+            |button, buttons| buttons.just_released(button),
+            |button, gamepad| gamepad.just_released(button),
+        )
+    }
+
+    fn any_binding(
+        &self,
+        action: Action,
+        key_check: impl Fn(KeyCode, &ButtonInput<KeyCode>) -> bool,
+        mouse_check: impl Fn(MouseButton, &ButtonInput<MouseButton>) -> bool,
+        gamepad_check: impl Fn(GamepadButton, &Gamepad) -> bool,
+    ) -> bool {
+        let control = self
+            .keys
+            .any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+        let shift = self
+            .keys
+            .any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+
+        self.bindings
+            .get(action)
+            .iter()
+            .any(|binding| match *binding {
+                Binding::Key(key) => !control && key_check(key, &self.keys),
+                Binding::ControlKey(key) => control && !shift && key_check(key, &self.keys),
+                Binding::ControlShiftKey(key) => control && shift && key_check(key, &self.keys),
+                Binding::Mouse(button) => mouse_check(button, &self.mouse_buttons),
+                Binding::Gamepad(button) => self
+                    .gamepads
+                    .iter()
+                    .any(|gamepad| gamepad_check(button, gamepad)),
+            })
+    }
+}
@@ -6,15 +6,24 @@
 use bevy::{prelude::*, reflect::hash_error};
 use bevy_tweening::TweeningPlugin;
 
+mod controls;
 mod game;
+mod input;
 mod main_menu;
+mod results;
+mod settings;
+mod settings_menu;
 mod style;
+#[cfg(test)]
+mod testing;
 mod utils;
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash, Default, States)]
 enum AppState {
     #[default]
     MainMenu,
+    Controls,
+    Settings,
     Game,
     Results,
 }
@@ -48,7 +57,12 @@ fn main() {
         .add_plugins(MeshPickingPlugin)
         .init_state::<AppState>()
         .add_sub_state::<GameState>()
+        .add_plugins(settings::plugin)
+        .add_plugins(style::plugin)
         .add_plugins(main_menu::plugin)
+        .add_plugins(controls::plugin)
+        .add_plugins(settings_menu::plugin)
         .add_plugins(game::plugin)
+        .add_plugins(results::plugin)
         .run();
 }
@@ -1,10 +1,12 @@
 use bevy::prelude::*;
 
-use crate::{AppState, style};
+use crate::{
+    AppState,
+    style::{ThemeColor, ThemedBackground, ThemedBorder, ThemedText},
+};
 
 pub(super) fn plugin(app: &mut App) {
-    app.insert_resource(ClearColor(style::BACKGROUND.into()))
-        .add_systems(OnEnter(AppState::MainMenu), setup_main_menu)
+    app.add_systems(OnEnter(AppState::MainMenu), setup_main_menu)
         .add_systems(OnExit(AppState::MainMenu), clean_up_main_menu);
     // app.add_systems(schedule, systems);
 }
@@ -32,28 +34,9 @@ fn setup_main_menu(mut commands: Commands, best_score: Res<crate::game::BestScor
             },
         ))
         .with_children(|parent| {
-            parent
-                .spawn((
-                    Button,
-                    Node {
-                        height: Val::Px(64.),
-                        width: Val::Percent(100.),
-                        ..default()
-                    },
-                    BorderColor(style::PRIMARY.into()),
-                    BackgroundColor(style::CONTAINER_PRIMARY.into()),
-                ))
-                .with_child((
-                    Text::new("Start"),
-                    TextFont {
-                        font_size: 33.0,
-                        ..default()
-                    },
-                    TextColor(style::ON_PRIMARY.into()),
-                ))
-                .observe(|_: Trigger<Pointer<Click>>, mut commands: Commands| {
-                    commands.set_state(AppState::Game);
-                });
+            spawn_menu_button(parent, "Start", AppState::Game);
+            spawn_menu_button(parent, "Controls", AppState::Controls);
+            spawn_menu_button(parent, "Settings", AppState::Settings);
 
             parent.spawn((
                 Text::new(format!("Best score: {}", best_score.0)),
@@ -61,11 +44,37 @@ fn setup_main_menu(mut commands: Commands, best_score: Res<crate::game::BestScor
                     font_size: 33.0,
                     ..default()
                 },
-                TextColor(style::SECONDARY_VARIANT.into()),
+                ThemedText(ThemeColor::Highlight),
             ));
         });
 }
 
+/// A button that goes to `state` when clicked.
+fn spawn_menu_button(parent: &mut ChildSpawnerCommands, label: &str, state: AppState) {
+    parent
+        .spawn((
+            Button,
+            Node {
+                height: Val::Px(64.),
+                width: Val::Percent(100.),
+                ..default()
+            },
+            ThemedBorder(ThemeColor::Primary),
+            ThemedBackground::new(ThemeColor::ContainerPrimary),
+        ))
+        .with_child((
+            Text::new(label),
+            TextFont {
+                font_size: 33.0,
+                ..default()
+            },
+            ThemedText(ThemeColor::OnPrimary),
+        ))
+        .observe(move |_: Trigger<Pointer<Click>>, mut commands: Commands| {
+            commands.set_state(state.clone());
+        });
+}
+
 fn clean_up_main_menu(
     mut commands: Commands,
     main_menu_components_query: Query<Entity, With<MainMenuComponent>>,
@@ -0,0 +1,105 @@
+use bevy::prelude::*;
+
+use crate::{
+    AppState,
+    game::{BestScore, metro::GameOver, satisfaction::Satisfaction, stations::kind_name},
+    style::{ThemeColor, ThemedBackground, ThemedBorder, ThemedText},
+};
+
+pub(super) fn plugin(app: &mut App) {
+    app.add_systems(OnEnter(AppState::Results), setup_results)
+        .add_systems(OnExit(AppState::Results), clean_up_results);
+}
+
+#[derive(Component)]
+struct ResultsComponent;
+
+fn setup_results(
+    mut commands: Commands,
+    satisfaction: Res<Satisfaction>,
+    best_score: Res<BestScore>,
+    game_over: Option<Res<GameOver>>,
+) {
+    commands.spawn((Camera2d, ResultsComponent));
+    commands
+        .spawn((
+            ResultsComponent,
+            Node {
+                justify_self: JustifySelf::Center,
+                align_self: AlignSelf::Center,
+                flex_direction: FlexDirection::Column,
+                row_gap: Val::Px(8.),
+                width: Val::Px(400.),
+                height: Val::Auto,
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            let cause = game_over.map_or_else(String::new, |game_over| {
+                format!(
+                    "The {} station ({:?}) was overcrowded for too long",
+                    kind_name(game_over.kind),
+                    game_over.district
+                )
+            });
+
+            for (line, font_size) in [
+                ("Game over".to_string(), 48.0),
+                (cause, 20.0),
+                (
+                    format!("Passengers delivered: {}", satisfaction.delivered),
+                    28.0,
+                ),
+                (
+                    format!("Passengers gave up: {}", satisfaction.abandoned),
+                    28.0,
+                ),
+                (
+                    format!("Satisfaction: {:.0}%", satisfaction.score() * 100.),
+                    28.0,
+                ),
+                (format!("Best score: {}", best_score.0), 28.0),
+            ] {
+                parent.spawn((
+                    Text::new(line),
+                    TextFont {
+                        font_size,
+                        ..default()
+                    },
+                    ThemedText(ThemeColor::OnBackground),
+                ));
+            }
+
+            parent
+                .spawn((
+                    Button,
+                    Node {
+                        height: Val::Px(64.),
+                        width: Val::Percent(100.),
+                        ..default()
+                    },
+                    ThemedBorder(ThemeColor::Primary),
+                    ThemedBackground::new(ThemeColor::ContainerPrimary),
+                ))
+                .with_child((
+                    Text::new("Main menu"),
+                    TextFont {
+                        font_size: 33.0,
+                        ..default()
+                    },
+                    ThemedText(ThemeColor::OnPrimary),
+                ))
+                .observe(|_: Trigger<Pointer<Click>>, mut commands: Commands| {
+                    commands.set_state(AppState::MainMenu);
+                });
+        });
+}
+
+fn clean_up_results(
+    mut commands: Commands,
+    results_components_query: Query<Entity, With<ResultsComponent>>,
+) {
+    for entity in results_components_query.iter() {
+        commands.entity(entity).despawn();
+    }
+}
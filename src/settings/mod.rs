@@ -0,0 +1,202 @@
+use std::{fs, io};
+
+use bevy::{
+    audio::Volume,
+    prelude::*,
+    window::{MonitorSelection, PresentMode, PrimaryWindow, WindowMode},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    game::{
+        clock::GAME_SPEEDS,
+        metro::{
+            COLOR_BLIND_LINE_COLORS, HIGH_CONTRAST_LINE_COLORS, LINE_COLORS, LINE_PATTERNS, LineId,
+            LinePattern,
+        },
+    },
+    input::Bindings,
+    style::{Theme, ThemeName},
+};
+
+/// Next to the `exports` directory, in the directory the game is started from.
+const SETTINGS_FILE: &str = "settings.json";
+
+pub(super) fn plugin(app: &mut App) {
+    let settings = load_settings();
+
+    app.insert_resource(Theme::new(settings.preferences.theme))
+        .insert_resource(settings.bindings)
+        .insert_resource(settings.preferences)
+        .add_systems(
+            Update,
+            (
+                apply_preferences.run_if(resource_changed::<Preferences>),
+                save_settings.run_if(
+                    (resource_changed::<Bindings>.or(resource_changed::<Preferences>))
+                        .and(not(resource_added::<Bindings>)),
+                ),
+            ),
+        );
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DisplayMode {
+    Windowed,
+    /// Borderless, on the monitor the window is on.
+    Fullscreen,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Palette {
+    Standard,
+    /// Line colours that stay apart with red-green colour blindness.
+    ColorBlind,
+    /// Fully saturated colours, made for the high contrast theme.
+    HighContrast,
+}
+
+impl Palette {
+    pub fn line_color(self, line_id: LineId) -> Srgba {
+        let colors = match self {
+            Palette::Standard => &LINE_COLORS,
+            Palette::ColorBlind => &COLOR_BLIND_LINE_COLORS,
+            Palette::HighContrast => &HIGH_CONTRAST_LINE_COLORS,
+        };
+
+        colors[line_id % colors.len()]
+    }
+}
+
+/// The options on the settings screen. Applied as soon as they change.
+#[derive(Resource, Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(default)]
+pub struct Preferences {
+    pub display_mode: DisplayMode,
+    pub vsync: bool,
+    pub ui_scale: f32,
+    pub theme: ThemeName,
+    /// From 0.0 to 1.0.
+    pub volume: f32,
+    pub palette: Palette,
+    /// Draws every line with its own dash pattern, on top of its colour.
+    pub line_patterns: bool,
+    /// Skips animations, e.g. the line indicators snap to their new size instead of bouncing.
+    pub reduced_motion: bool,
+    /// Index into `game::clock::GAME_SPEEDS`, used at the start of every run.
+    pub default_game_speed: usize,
+}
+
+impl Preferences {
+    pub fn starting_game_speed(&self) -> f32 {
+        GAME_SPEEDS
+            .get(self.default_game_speed)
+            .copied()
+            .unwrap_or(GAME_SPEEDS[0])
+    }
+
+    pub fn line_color(&self, line_id: LineId) -> Srgba {
+        self.palette.line_color(line_id)
+    }
+
+    pub fn line_pattern(&self, line_id: LineId) -> LinePattern {
+        if self.line_patterns {
+            LINE_PATTERNS[line_id % LINE_PATTERNS.len()]
+        } else {
+            LinePattern::Solid
+        }
+    }
+}
+
+impl Default for Preferences {
+    fn default() -> Self {
+        Preferences {
+            display_mode: DisplayMode::Windowed,
+            vsync: true,
+            ui_scale: 1.,
+            theme: ThemeName::Dark,
+            volume: 1.,
+            palette: Palette::Standard,
+            line_patterns: false,
+            reduced_motion: false,
+            default_game_speed: 0,
+        }
+    }
+}
+
+/// Everything that's kept between runs of the game. Missing fields get their default values, so that
+/// files written by older versions still load.
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+struct Settings {
+    bindings: Bindings,
+    preferences: Preferences,
+}
+
+fn load_settings() -> Settings {
+    let json = match fs::read_to_string(SETTINGS_FILE) {
+        Ok(json) => json,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Settings::default(),
+        Err(err) => {
+            warn!("couldn't read {SETTINGS_FILE}: {err}");
+            return Settings::default();
+        }
+    };
+
+    match serde_json::from_str::<Settings>(&json) {
+        Ok(mut settings) => {
+            settings.bindings.fill_missing();
+            settings
+        }
+        Err(err) => {
+            warn!("couldn't parse {SETTINGS_FILE}, using the defaults: {err}");
+            Settings::default()
+        }
+    }
+}
+
+fn save_settings(bindings: Res<Bindings>, preferences: Res<Preferences>) {
+    let settings = Settings {
+        bindings: bindings.clone(),
+        preferences: preferences.clone(),
+    };
+
+    let result = serde_json::to_string_pretty(&settings)
+        .map_err(io::Error::other)
+        .and_then(|json| fs::write(SETTINGS_FILE, json));
+
+    if let Err(err) = result {
+        error!("couldn't save {SETTINGS_FILE}: {err}");
+    }
+}
+
+fn apply_preferences(
+    mut window: Single<&mut Window, With<PrimaryWindow>>,
+    mut theme: ResMut<Theme>,
+    mut ui_scale: ResMut<UiScale>,
+    mut global_volume: ResMut<GlobalVolume>,
+    preferences: Res<Preferences>,
+) {
+    let mode = match preferences.display_mode {
+        DisplayMode::Windowed => WindowMode::Windowed,
+        DisplayMode::Fullscreen => WindowMode::BorderlessFullscreen(MonitorSelection::Current),
+    };
+    if window.mode != mode {
+        window.mode = mode;
+    }
+
+    let present_mode = if preferences.vsync {
+        PresentMode::AutoVsync
+    } else {
+        PresentMode::AutoNoVsync
+    };
+    if window.present_mode != present_mode {
+        window.present_mode = present_mode;
+    }
+
+    if theme.name != preferences.theme {
+        *theme = Theme::new(preferences.theme);
+    }
+    ui_scale.0 = preferences.ui_scale;
+    global_volume.volume = Volume::Linear(preferences.volume);
+}
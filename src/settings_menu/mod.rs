@@ -0,0 +1,264 @@
+use bevy::prelude::*;
+
+use crate::{
+    AppState,
+    game::clock::GAME_SPEEDS,
+    settings::{DisplayMode, Palette, Preferences},
+    style::{ThemeColor, ThemeName, ThemedBackground, ThemedBorder, ThemedText},
+};
+
+const ROW_HEIGHT: f32 = 40.;
+const UI_SCALES: [f32; 4] = [0.75, 1., 1.25, 1.5];
+const VOLUMES: [f32; 5] = [0., 0.25, 0.5, 0.75, 1.];
+
+pub(super) fn plugin(app: &mut App) {
+    app.add_systems(OnEnter(AppState::Settings), setup_settings_menu)
+        .add_systems(OnExit(AppState::Settings), clean_up_settings_menu)
+        .add_systems(
+            Update,
+            update_option_texts
+                .run_if(in_state(AppState::Settings))
+                .run_if(resource_changed::<Preferences>),
+        );
+}
+
+#[derive(Component)]
+struct SettingsMenuComponent;
+
+/// One row of the settings screen. Clicking its value switches to the next one.
+#[derive(Component, Clone, Copy)]
+enum SettingsOption {
+    DisplayMode,
+    Vsync,
+    UiScale,
+    Theme,
+    Volume,
+    Palette,
+    LinePatterns,
+    ReducedMotion,
+    DefaultGameSpeed,
+}
+
+impl SettingsOption {
+    const ALL: [SettingsOption; 9] = [
+        SettingsOption::DisplayMode,
+        SettingsOption::Vsync,
+        SettingsOption::UiScale,
+        SettingsOption::Theme,
+        SettingsOption::Volume,
+        SettingsOption::Palette,
+        SettingsOption::LinePatterns,
+        SettingsOption::ReducedMotion,
+        SettingsOption::DefaultGameSpeed,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            SettingsOption::DisplayMode => "Window mode",
+            SettingsOption::Vsync => "Vsync",
+            SettingsOption::UiScale => "UI scale",
+            SettingsOption::Theme => "Theme",
+            SettingsOption::Volume => "Volume",
+            SettingsOption::Palette => "Colour palette",
+            SettingsOption::LinePatterns => "Line patterns",
+            SettingsOption::ReducedMotion => "Reduced motion",
+            SettingsOption::DefaultGameSpeed => "Default game speed",
+        }
+    }
+
+    fn value(self, preferences: &Preferences) -> String {
+        let on_off = |value: bool| if value { "On" } else { "Off" }.to_string();
+
+        match self {
+            SettingsOption::DisplayMode => format!("{:?}", preferences.display_mode),
+            SettingsOption::Vsync => on_off(preferences.vsync),
+            SettingsOption::UiScale => format!("{:.0}%", preferences.ui_scale * 100.),
+            SettingsOption::Theme => match preferences.theme {
+                ThemeName::Light => "Light".to_string(),
+                ThemeName::Dark => "Dark".to_string(),
+                ThemeName::NightMap => "Night map".to_string(),
+                ThemeName::HighContrast => "High contrast".to_string(),
+            },
+            SettingsOption::Volume => format!("{:.0}%", preferences.volume * 100.),
+            SettingsOption::Palette => match preferences.palette {
+                Palette::Standard => "Standard".to_string(),
+                Palette::ColorBlind => "Colour blind".to_string(),
+                Palette::HighContrast => "High contrast".to_string(),
+            },
+            SettingsOption::LinePatterns => on_off(preferences.line_patterns),
+            SettingsOption::ReducedMotion => on_off(preferences.reduced_motion),
+            SettingsOption::DefaultGameSpeed => {
+                format!("{}x", preferences.starting_game_speed())
+            }
+        }
+    }
+
+    fn cycle(self, preferences: &mut Preferences) {
+        match self {
+            SettingsOption::DisplayMode => {
+                preferences.display_mode = match preferences.display_mode {
+                    DisplayMode::Windowed => DisplayMode::Fullscreen,
+                    DisplayMode::Fullscreen => DisplayMode::Windowed,
+                }
+            }
+            SettingsOption::Vsync => preferences.vsync = !preferences.vsync,
+            SettingsOption::UiScale => {
+                preferences.ui_scale = next(&UI_SCALES, preferences.ui_scale)
+            }
+            SettingsOption::Theme => {
+                preferences.theme = match preferences.theme {
+                    ThemeName::Light => ThemeName::Dark,
+                    ThemeName::Dark => ThemeName::NightMap,
+                    ThemeName::NightMap => ThemeName::HighContrast,
+                    ThemeName::HighContrast => ThemeName::Light,
+                }
+            }
+            SettingsOption::Volume => preferences.volume = next(&VOLUMES, preferences.volume),
+            SettingsOption::Palette => {
+                preferences.palette = match preferences.palette {
+                    Palette::Standard => Palette::ColorBlind,
+                    Palette::ColorBlind => Palette::HighContrast,
+                    Palette::HighContrast => Palette::Standard,
+                }
+            }
+            SettingsOption::LinePatterns => preferences.line_patterns = !preferences.line_patterns,
+            SettingsOption::ReducedMotion => {
+                preferences.reduced_motion = !preferences.reduced_motion
+            }
+            SettingsOption::DefaultGameSpeed => {
+                preferences.default_game_speed =
+                    (preferences.default_game_speed + 1) % GAME_SPEEDS.len()
+            }
+        }
+    }
+}
+
+/// The value after the one closest to `current`, wrapping around. Values from an edited settings
+/// file don't have to be one of the steps.
+fn next(steps: &[f32], current: f32) -> f32 {
+    let closest = (0..steps.len())
+        .min_by(|a, b| {
+            (steps[*a] - current)
+                .abs()
+                .total_cmp(&(steps[*b] - current).abs())
+        })
+        .unwrap_or_default();
+
+    steps[(closest + 1) % steps.len()]
+}
+
+fn setup_settings_menu(mut commands: Commands, preferences: Res<Preferences>) {
+    commands.spawn((Camera2d, SettingsMenuComponent));
+    commands
+        .spawn((
+            SettingsMenuComponent,
+            Node {
+                justify_self: JustifySelf::Center,
+                align_self: AlignSelf::Center,
+                flex_direction: FlexDirection::Column,
+                row_gap: Val::Px(8.),
+                width: Val::Px(520.),
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                Text::new("Settings"),
+                TextFont {
+                    font_size: 48.0,
+                    ..default()
+                },
+                ThemedText(ThemeColor::OnBackground),
+            ));
+
+            for option in SettingsOption::ALL {
+                parent
+                    .spawn(Node {
+                        height: Val::Px(ROW_HEIGHT),
+                        justify_content: JustifyContent::SpaceBetween,
+                        align_items: AlignItems::Center,
+                        ..default()
+                    })
+                    .with_children(|row| {
+                        row.spawn((
+                            Text::new(option.label()),
+                            TextFont {
+                                font_size: 22.0,
+                                ..default()
+                            },
+                            ThemedText(ThemeColor::OnBackground),
+                        ));
+
+                        row.spawn((
+                            Button,
+                            Node {
+                                width: Val::Px(200.),
+                                height: Val::Percent(100.),
+                                justify_content: JustifyContent::Center,
+                                align_items: AlignItems::Center,
+                                ..default()
+                            },
+                            ThemedBackground::new(ThemeColor::Surface),
+                            BorderRadius::all(Val::Px(4.)),
+                        ))
+                        .with_child((
+                            option,
+                            Text::new(option.value(&preferences)),
+                            TextFont {
+                                font_size: 20.0,
+                                ..default()
+                            },
+                            ThemedText(ThemeColor::OnSurface),
+                            Pickable::IGNORE,
+                        ))
+                        .observe(
+                            move |_: Trigger<Pointer<Click>>,
+                                  mut preferences: ResMut<Preferences>| {
+                                option.cycle(&mut preferences);
+                            },
+                        );
+                    });
+            }
+
+            parent
+                .spawn((
+                    Button,
+                    Node {
+                        height: Val::Px(64.),
+                        width: Val::Percent(100.),
+                        ..default()
+                    },
+                    ThemedBorder(ThemeColor::Primary),
+                    ThemedBackground::new(ThemeColor::ContainerPrimary),
+                ))
+                .with_child((
+                    Text::new("Back"),
+                    TextFont {
+                        font_size: 33.0,
+                        ..default()
+                    },
+                    ThemedText(ThemeColor::OnPrimary),
+                ))
+                .observe(|_: Trigger<Pointer<Click>>, mut commands: Commands| {
+                    commands.set_state(AppState::MainMenu);
+                });
+        });
+}
+
+fn update_option_texts(
+    mut texts_q: Query<(&SettingsOption, &mut Text)>,
+    preferences: Res<Preferences>,
+) {
+    for (option, mut text) in &mut texts_q {
+        text.0 = option.value(&preferences);
+    }
+}
+
+fn clean_up_settings_menu(
+    mut commands: Commands,
+    settings_menu_components_query: Query<Entity, With<SettingsMenuComponent>>,
+) {
+    for entity in settings_menu_components_query.iter() {
+        commands.entity(entity).despawn();
+    }
+}
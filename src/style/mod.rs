@@ -1,6 +1,7 @@
 #![allow(dead_code)]
 
-use bevy::color::Srgba;
+use bevy::{color::palettes::basic as colors, prelude::*};
+use serde::{Deserialize, Serialize};
 
 pub const PRIMARY: Srgba = Srgba::new(0.2, 0.8, 0.4, 1.0); // Vibrant green
 pub const PRIMARY_VARIANT: Srgba = Srgba::new(0.0, 0.6, 0.3, 1.0); // Darker green
@@ -33,3 +34,234 @@ pub const GRADIENT_GREEN_START: Srgba = Srgba::new(0.2, 0.7, 0.4, 1.0);
 pub const GRADIENT_GREEN_END: Srgba = Srgba::new(0.0, 0.5, 0.2, 1.0);
 pub const GRADIENT_PURPLE_START: Srgba = Srgba::new(0.5, 0.3, 0.7, 1.0);
 pub const GRADIENT_PURPLE_END: Srgba = Srgba::new(0.3, 0.1, 0.5, 1.0);
+
+pub(super) fn plugin(app: &mut App) {
+    app.add_systems(
+        PostUpdate,
+        (
+            apply_clear_color.run_if(resource_changed::<Theme>),
+            apply_themed_backgrounds,
+            apply_themed_texts,
+            apply_themed_borders,
+        ),
+    );
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ThemeName {
+    Light,
+    Dark,
+    /// Deep blue, like a metro map at night.
+    NightMap,
+    HighContrast,
+}
+
+/// The colours of everything that isn't a line. Replaced as a whole when the theme in
+/// `settings::Preferences` changes, which restyles every entity with a `Themed*` component.
+#[derive(Resource, Clone, PartialEq, Debug)]
+pub struct Theme {
+    pub name: ThemeName,
+    pub background: Srgba,
+    pub surface: Srgba,
+    pub on_background: Srgba,
+    pub on_surface: Srgba,
+    /// Accents, e.g. the selected line indicator and the station a line can be connected to.
+    pub primary: Srgba,
+    pub on_primary: Srgba,
+    pub container_primary: Srgba,
+    pub container_secondary: Srgba,
+    pub on_secondary: Srgba,
+    /// Hints and other less important text.
+    pub muted: Srgba,
+    /// Text that should stand out from the rest, like the best score.
+    pub highlight: Srgba,
+    pub error: Srgba,
+    pub station_fill: Srgba,
+    pub station_border: Srgba,
+    /// The passengers waiting next to the stations.
+    pub passenger: Srgba,
+}
+
+impl Theme {
+    pub fn new(name: ThemeName) -> Self {
+        match name {
+            ThemeName::Light => Theme {
+                name,
+                background: Srgba::new(0.95, 0.95, 0.93, 1.0),
+                surface: Srgba::new(0.87, 0.87, 0.85, 1.0),
+                on_background: Srgba::new(0.1, 0.1, 0.1, 1.0),
+                on_surface: Srgba::new(0.1, 0.1, 0.1, 1.0),
+                primary: Srgba::new(0.1, 0.6, 0.3, 1.0),
+                on_primary: Srgba::new(0.05, 0.05, 0.05, 1.0),
+                container_primary: Srgba::new(0.55, 0.85, 0.65, 1.0),
+                container_secondary: Srgba::new(0.8, 0.72, 0.9, 1.0),
+                on_secondary: Srgba::new(0.1, 0.1, 0.1, 1.0),
+                muted: Srgba::new(0.45, 0.45, 0.45, 1.0),
+                highlight: SECONDARY_VARIANT,
+                error: Srgba::new(0.8, 0.1, 0.1, 1.0),
+                station_fill: colors::WHITE,
+                station_border: Srgba::new(0.15, 0.15, 0.15, 1.0),
+                passenger: Srgba::new(0.2, 0.2, 0.2, 1.0),
+            },
+            ThemeName::Dark => Theme {
+                name,
+                background: BACKGROUND,
+                surface: SURFACE,
+                on_background: ON_BACKGROUND,
+                on_surface: ON_SURFACE,
+                primary: PRIMARY,
+                on_primary: ON_PRIMARY,
+                container_primary: CONTAINER_PRIMARY,
+                container_secondary: CONTAINER_SECONDARY,
+                on_secondary: ON_SECONDARY,
+                muted: ACCENT_GRAY_MEDIUM,
+                highlight: SECONDARY_VARIANT,
+                error: ERROR,
+                station_fill: colors::GRAY,
+                station_border: colors::WHITE,
+                passenger: ON_BACKGROUND,
+            },
+            ThemeName::NightMap => Theme {
+                name,
+                background: Srgba::new(0.03, 0.05, 0.12, 1.0),
+                surface: Srgba::new(0.07, 0.1, 0.2, 1.0),
+                on_background: Srgba::new(0.8, 0.85, 0.95, 1.0),
+                on_surface: Srgba::new(0.8, 0.85, 0.95, 1.0),
+                primary: Srgba::new(0.95, 0.75, 0.3, 1.0),
+                on_primary: Srgba::new(0.05, 0.05, 0.1, 1.0),
+                container_primary: Srgba::new(0.75, 0.55, 0.2, 1.0),
+                container_secondary: Srgba::new(0.2, 0.2, 0.4, 1.0),
+                on_secondary: Srgba::new(0.9, 0.9, 0.95, 1.0),
+                muted: Srgba::new(0.4, 0.45, 0.6, 1.0),
+                highlight: Srgba::new(0.6, 0.65, 0.9, 1.0),
+                error: Srgba::new(1.0, 0.35, 0.35, 1.0),
+                station_fill: Srgba::new(0.1, 0.13, 0.25, 1.0),
+                station_border: Srgba::new(0.95, 0.85, 0.6, 1.0),
+                passenger: Srgba::new(0.8, 0.85, 0.95, 1.0),
+            },
+            ThemeName::HighContrast => Theme {
+                name,
+                background: colors::BLACK,
+                surface: Srgba::new(0.12, 0.12, 0.12, 1.0),
+                on_background: colors::WHITE,
+                on_surface: colors::WHITE,
+                primary: colors::YELLOW,
+                on_primary: colors::BLACK,
+                container_primary: colors::YELLOW,
+                container_secondary: Srgba::new(0.0, 0.3, 0.8, 1.0),
+                on_secondary: colors::WHITE,
+                muted: Srgba::new(0.75, 0.75, 0.75, 1.0),
+                highlight: colors::AQUA,
+                error: Srgba::new(1.0, 0.2, 0.2, 1.0),
+                station_fill: colors::BLACK,
+                station_border: colors::WHITE,
+                passenger: colors::WHITE,
+            },
+        }
+    }
+
+    pub fn color(&self, color: ThemeColor) -> Srgba {
+        match color {
+            ThemeColor::Background => self.background,
+            ThemeColor::Surface => self.surface,
+            ThemeColor::OnBackground => self.on_background,
+            ThemeColor::OnSurface => self.on_surface,
+            ThemeColor::Primary => self.primary,
+            ThemeColor::OnPrimary => self.on_primary,
+            ThemeColor::ContainerPrimary => self.container_primary,
+            ThemeColor::ContainerSecondary => self.container_secondary,
+            ThemeColor::OnSecondary => self.on_secondary,
+            ThemeColor::Muted => self.muted,
+            ThemeColor::Highlight => self.highlight,
+            ThemeColor::Error => self.error,
+        }
+    }
+}
+
+/// One of the `Theme` colours, for the `Themed*` components.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ThemeColor {
+    Background,
+    Surface,
+    OnBackground,
+    OnSurface,
+    Primary,
+    OnPrimary,
+    ContainerPrimary,
+    ContainerSecondary,
+    OnSecondary,
+    Muted,
+    Highlight,
+    Error,
+}
+
+/// Keeps the `BackgroundColor` of a UI node in sync with the theme.
+#[derive(Component, Clone, Copy)]
+#[require(BackgroundColor)]
+pub struct ThemedBackground {
+    pub color: ThemeColor,
+    pub alpha: f32,
+}
+
+impl ThemedBackground {
+    pub fn new(color: ThemeColor) -> Self {
+        ThemedBackground { color, alpha: 1. }
+    }
+
+    pub fn with_alpha(self, alpha: f32) -> Self {
+        ThemedBackground { alpha, ..self }
+    }
+}
+
+/// Keeps the `TextColor` in sync with the theme.
+#[derive(Component, Clone, Copy)]
+#[require(TextColor)]
+pub struct ThemedText(pub ThemeColor);
+
+/// Keeps the `BorderColor` of a UI node in sync with the theme.
+#[derive(Component, Clone, Copy)]
+#[require(BorderColor)]
+pub struct ThemedBorder(pub ThemeColor);
+
+/// Moves an absolutely positioned node to `position`, in logical window pixels like pointer
+/// positions, plus `offset` in UI pixels. `Val::Px` is scaled by `UiScale`, so the position is
+/// divided by it first.
+pub fn place_node(node: &mut Node, position: Vec2, offset: Vec2, ui_scale: &UiScale) {
+    let position = position / ui_scale.0 + offset;
+    node.left = Val::Px(position.x);
+    node.top = Val::Px(position.y);
+}
+
+fn apply_clear_color(mut clear_color: ResMut<ClearColor>, theme: Res<Theme>) {
+    clear_color.0 = theme.background.into();
+}
+
+fn apply_themed_backgrounds(
+    mut nodes_q: Query<(Ref<ThemedBackground>, &mut BackgroundColor)>,
+    theme: Res<Theme>,
+) {
+    for (themed, mut background) in &mut nodes_q {
+        if theme.is_changed() || themed.is_changed() {
+            background.0 = theme.color(themed.color).with_alpha(themed.alpha).into();
+        }
+    }
+}
+
+fn apply_themed_texts(mut texts_q: Query<(Ref<ThemedText>, &mut TextColor)>, theme: Res<Theme>) {
+    for (themed, mut text_color) in &mut texts_q {
+        if theme.is_changed() || themed.is_changed() {
+            text_color.0 = theme.color(themed.0).into();
+        }
+    }
+}
+
+fn apply_themed_borders(
+    mut nodes_q: Query<(Ref<ThemedBorder>, &mut BorderColor)>,
+    theme: Res<Theme>,
+) {
+    for (themed, mut border_color) in &mut nodes_q {
+        if theme.is_changed() || themed.is_changed() {
+            border_color.0 = theme.color(themed.0).into();
+        }
+    }
+}
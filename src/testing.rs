@@ -0,0 +1,139 @@
+//! Helpers for running parts of the game in a headless `App`, with input injected the way the
+//! windowing backend would send it.
+
+use std::time::Duration;
+
+use bevy::{
+    input::{
+        InputPlugin,
+        gamepad::{RawGamepadAxisChangedEvent, RawGamepadButtonChangedEvent, RawGamepadEvent},
+        touch::{TouchInput, TouchPhase},
+    },
+    picking::{
+        PickSet,
+        backend::{HitData, PointerHits},
+        pointer::{PointerId, PointerLocation},
+    },
+    prelude::*,
+    render::camera::CameraPlugin,
+    state::app::StatesPlugin,
+    time::TimeUpdateStrategy,
+    window::{ExitCondition, PrimaryWindow, WindowEvent, WindowResolution},
+};
+
+use crate::{AppState, GameState, input::Bindings};
+
+/// Real time that passes with every `App::update`.
+pub const FRAME_TIME: Duration = Duration::from_millis(100);
+pub const WINDOW_SIZE: Vec2 = Vec2::new(800., 600.);
+
+/// The entity that's under every pointer, as far as picking is concerned. Stands in for the mesh
+/// picking backend, which needs real meshes.
+#[derive(Resource, Default)]
+pub struct PickedEntity(pub Option<Entity>);
+
+/// An app that's in the game, with a window, a camera looking into it, input and picking, but
+/// nothing rendered. The game's own plugins are added by the tests that need them.
+pub fn headless_app() -> App {
+    let mut app = App::new();
+
+    app.add_plugins((
+        MinimalPlugins,
+        StatesPlugin,
+        InputPlugin,
+        WindowPlugin {
+            primary_window: Some(Window {
+                resolution: WindowResolution::new(WINDOW_SIZE.x, WINDOW_SIZE.y),
+                ..default()
+            }),
+            exit_condition: ExitCondition::DontExit,
+            close_when_requested: false,
+        },
+        AssetPlugin::default(),
+        TransformPlugin,
+        CameraPlugin,
+        DefaultPickingPlugins,
+    ))
+    .init_asset::<Image>()
+    .insert_resource(TimeUpdateStrategy::ManualDuration(FRAME_TIME))
+    .init_resource::<Bindings>()
+    .init_resource::<PickedEntity>()
+    .init_resource::<UiScale>()
+    .insert_state(AppState::Game)
+    .add_sub_state::<GameState>()
+    .add_systems(PreUpdate, pick_entity.in_set(PickSet::Backend));
+
+    app.world_mut().spawn(Camera2d);
+
+    app
+}
+
+fn pick_entity(
+    pointers_q: Query<(&PointerId, &PointerLocation)>,
+    camera: Single<Entity, With<Camera>>,
+    picked_entity: Res<PickedEntity>,
+    mut hits_events: EventWriter<PointerHits>,
+) {
+    let Some(entity) = picked_entity.0 else {
+        return;
+    };
+
+    for (pointer_id, _) in pointers_q
+        .iter()
+        .filter(|(_, location)| location.location.is_some())
+    {
+        hits_events.write(PointerHits::new(
+            *pointer_id,
+            vec![(entity, HitData::new(*camera, 0., None, None))],
+            0.,
+        ));
+    }
+}
+
+/// Sends a touch event both to `Touches` and to picking, like winit does.
+pub fn touch(app: &mut App, id: u64, phase: TouchPhase, position: Vec2) {
+    let world = app.world_mut();
+    let window = world
+        .query_filtered::<Entity, With<PrimaryWindow>>()
+        .single(world)
+        .unwrap();
+
+    let touch = TouchInput {
+        phase,
+        position,
+        window,
+        force: None,
+        id,
+    };
+    world.send_event(touch);
+    world.send_event(WindowEvent::TouchInput(touch));
+}
+
+/// A connected gamepad with nothing pressed.
+pub fn spawn_gamepad(app: &mut App) -> Entity {
+    app.world_mut().spawn(Gamepad::default()).id()
+}
+
+/// Sets how far `button` is pressed, from 0 to 1.
+pub fn set_gamepad_button(app: &mut App, gamepad: Entity, button: GamepadButton, value: f32) {
+    app.world_mut()
+        .send_event(RawGamepadEvent::Button(RawGamepadButtonChangedEvent::new(
+            gamepad, button, value,
+        )));
+}
+
+/// Sets the position of `axis`, from -1 to 1.
+pub fn set_gamepad_axis(app: &mut App, gamepad: Entity, axis: GamepadAxis, value: f32) {
+    app.world_mut()
+        .send_event(RawGamepadEvent::Axis(RawGamepadAxisChangedEvent::new(
+            gamepad, axis, value,
+        )));
+}
+
+/// Presses `button` for one frame.
+pub fn tap_gamepad_button(app: &mut App, gamepad: Entity, button: GamepadButton) {
+    set_gamepad_button(app, gamepad, button, 1.);
+    app.update();
+    set_gamepad_button(app, gamepad, button, 0.);
+    app.update();
+}